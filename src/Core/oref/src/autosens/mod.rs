@@ -8,7 +8,7 @@
 use chrono::{DateTime, Duration, Timelike, Utc};
 use crate::types::{AutosensData, GlucoseReading, Profile, Treatment, TempTarget};
 use crate::insulin::calculate_iob_contrib;
-use crate::profile::{isf_lookup, basal_lookup};
+use crate::profile::{effective_isf_lookup, basal_lookup};
 use crate::Result;
 
 /// Configuration for autosens detection
@@ -64,6 +64,9 @@ pub fn detect_sensitivity(
 }
 
 /// Detect sensitivity with full configuration
+///
+/// Thin wrapper around [`detect_sensitivity_detailed`] for callers that only
+/// need the bare ratio.
 pub fn detect_sensitivity_with_config(
     profile: &Profile,
     glucose_data: &[GlucoseReading],
@@ -72,8 +75,24 @@ pub fn detect_sensitivity_with_config(
     clock: DateTime<Utc>,
     config: &AutosensConfig,
 ) -> Result<AutosensData> {
+    let detailed = detect_sensitivity_detailed(profile, glucose_data, treatments, temp_targets, clock, config)?;
+    Ok(AutosensData { ratio: detailed.ratio })
+}
+
+/// Detect sensitivity and return the full [`SensitivityResult`], including
+/// how many real (non-padding) deviations drove the ratio, their average,
+/// and the resulting [`SensitivityCategory`] - so UIs and logging can
+/// explain *why* a ratio was chosen rather than just showing the number.
+pub fn detect_sensitivity_detailed(
+    profile: &Profile,
+    glucose_data: &[GlucoseReading],
+    treatments: &[Treatment],
+    temp_targets: &[TempTarget],
+    clock: DateTime<Utc>,
+    config: &AutosensConfig,
+) -> Result<SensitivityResult> {
     if glucose_data.is_empty() {
-        return Ok(AutosensData { ratio: 1.0 });
+        return Ok(SensitivityResult::neutral());
     }
 
     // Determine last site change (default to 24 hours ago)
@@ -96,7 +115,7 @@ pub fn detect_sensitivity_with_config(
     let bucketed_data = bucket_glucose_data_for_autosens(glucose_data, last_site_change);
 
     if bucketed_data.len() < 4 {
-        return Ok(AutosensData { ratio: 1.0 });
+        return Ok(SensitivityResult::neutral());
     }
 
     // Find meal treatments for exclusion
@@ -113,13 +132,29 @@ pub fn detect_sensitivity_with_config(
     )?;
 
     if deviations.is_empty() {
-        return Ok(AutosensData { ratio: 1.0 });
+        return Ok(SensitivityResult::neutral());
     }
 
+    // Zero-padding (added to dampen sparse data) shouldn't count as a real
+    // deviation that drove the ratio
+    let real_deviations: Vec<f64> = deviations.iter().copied().filter(|d| d.abs() > 0.0001).collect();
+    let deviation_count = real_deviations.len();
+    let avg_deviation = if deviation_count > 0 {
+        real_deviations.iter().sum::<f64>() / deviation_count as f64
+    } else {
+        0.0
+    };
+
     // Calculate sensitivity ratio from deviations
     let ratio = calculate_ratio_from_deviations(&deviations, profile, config);
-
-    Ok(AutosensData { ratio })
+    let category = SensitivityCategory::from(ratio);
+
+    Ok(SensitivityResult {
+        ratio,
+        deviation_count,
+        avg_deviation,
+        category,
+    })
 }
 
 /// Bucketed glucose data point
@@ -265,7 +300,7 @@ fn calculate_deviations(
         let delta = bg - last_bg;
 
         // Get sensitivity at this time
-        let sens = isf_lookup(profile, bg_datetime);
+        let sens = effective_isf_lookup(profile, treatments, bg, bg_datetime);
 
         // Calculate IOB at this time
         let iob = calculate_iob_at_time(profile, treatments, bg_datetime);
@@ -379,7 +414,17 @@ fn calculate_deviations(
     Ok(deviations)
 }
 
-/// Calculate sensitivity ratio from deviations
+/// Calculate sensitivity ratio from deviations, detecting both sensitivity
+/// (ratio < 1) and resistance (ratio > 1)
+///
+/// A plain median underreacts to a strong one-sided deviation pattern, so
+/// this picks a percentile on each side of the median that widens as more
+/// real (non-padding) deviations accumulate - [`sensitivity_percentile`] -
+/// then computes a candidate ratio from whichever side's deviation is
+/// actually one-sided (negative on the low side -> sensitive, positive on
+/// the high side -> resistant), and returns whichever candidate deviates
+/// further from 1.0. This lets the detector find resistance as aggressively
+/// as sensitivity, which a median-only approach misses.
 fn calculate_ratio_from_deviations(
     deviations: &[f64],
     profile: &Profile,
@@ -389,17 +434,35 @@ fn calculate_ratio_from_deviations(
         return 1.0;
     }
 
+    // Zero-padding (added to dampen sparse data) shouldn't count toward how
+    // much real data we have when choosing the percentile spread
+    let real_count = deviations.iter().filter(|d| d.abs() > 0.0001).count();
+
     let mut sorted = deviations.to_vec();
     sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
 
-    // Get 50th percentile (median)
-    let p50 = percentile(&sorted, 0.50);
+    let pct = sensitivity_percentile(real_count);
+    let low_deviation = percentile(&sorted, pct);
+    let high_deviation = percentile(&sorted, 1.0 - pct);
+
+    let candidate_ratio = |deviation: f64| {
+        let basal_off = deviation * (60.0 / 5.0) / profile.sens;
+        1.0 + (basal_off / profile.max_basal)
+    };
 
-    // Calculate basal offset based on median deviation
-    let basal_off = p50 * (60.0 / 5.0) / profile.sens;
+    let sensitive_ratio = (low_deviation < 0.0).then(|| candidate_ratio(low_deviation));
+    let resistant_ratio = (high_deviation > 0.0).then(|| candidate_ratio(high_deviation));
 
-    // Calculate raw ratio
-    let raw_ratio = 1.0 + (basal_off / profile.max_basal);
+    let raw_ratio = [sensitive_ratio, resistant_ratio]
+        .into_iter()
+        .flatten()
+        .fold(1.0, |best, candidate| {
+            if (candidate - 1.0).abs() > (best - 1.0).abs() {
+                candidate
+            } else {
+                best
+            }
+        });
 
     // Clamp to configured limits
     let ratio = raw_ratio
@@ -410,6 +473,25 @@ fn calculate_ratio_from_deviations(
     (ratio * 100.0).round() / 100.0
 }
 
+/// Percentile used to pick the "one-sided" deviation a sensitivity or
+/// resistance candidate ratio is based on, scaled by how much real
+/// (non-padding) data is available
+///
+/// Sparse data (`real_count` near 0) stays close to the 50th percentile
+/// (median) to avoid over-reacting to a handful of points; a fuller
+/// deviation history (`real_count` at or above 100) widens toward the 20th
+/// percentile to catch a real one-sided pattern rather than averaging it away.
+fn sensitivity_percentile(real_count: usize) -> f64 {
+    const MIN_REAL_COUNT: f64 = 10.0;
+    const FULL_REAL_COUNT: f64 = 100.0;
+    const MAX_SPREAD: f64 = 0.30;
+
+    let scale = ((real_count as f64 - MIN_REAL_COUNT) / (FULL_REAL_COUNT - MIN_REAL_COUNT))
+        .clamp(0.0, 1.0);
+
+    0.50 - scale * MAX_SPREAD
+}
+
 /// Calculate percentile of sorted values
 fn percentile(sorted: &[f64], p: f64) -> f64 {
     if sorted.is_empty() {
@@ -515,6 +597,19 @@ pub struct SensitivityResult {
     pub category: SensitivityCategory,
 }
 
+impl SensitivityResult {
+    /// A neutral result (ratio 1.0, no deviations) for early-exit cases
+    /// where there isn't enough data to detect sensitivity
+    fn neutral() -> Self {
+        Self {
+            ratio: 1.0,
+            deviation_count: 0,
+            avg_deviation: 0.0,
+            category: SensitivityCategory::Normal,
+        }
+    }
+}
+
 /// Sensitivity category
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SensitivityCategory {
@@ -562,4 +657,94 @@ mod tests {
         assert_eq!(round_to_decimal(1.2345, 2), 1.23);
         assert_eq!(round_to_decimal(1.2355, 2), 1.24);
     }
+
+    #[test]
+    fn test_sensitivity_percentile_widens_with_more_real_data() {
+        let sparse = sensitivity_percentile(0);
+        let full = sensitivity_percentile(200);
+
+        assert!((sparse - 0.50).abs() < 0.001);
+        assert!((full - 0.20).abs() < 0.001);
+        assert!(full < sparse);
+    }
+
+    fn make_autosens_profile() -> Profile {
+        Profile {
+            sens: 50.0,
+            max_basal: 2.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_calculate_ratio_detects_resistance() {
+        // All positive deviations, lots of real data -> should detect
+        // resistance (ratio > 1)
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+        let deviations: Vec<f64> = (0..150).map(|i| 5.0 + (i % 3) as f64).collect();
+
+        let ratio = calculate_ratio_from_deviations(&deviations, &profile, &config);
+
+        assert!(ratio > 1.0);
+    }
+
+    #[test]
+    fn test_calculate_ratio_detects_sensitivity() {
+        // All negative deviations, lots of real data -> should detect
+        // sensitivity (ratio < 1)
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+        let deviations: Vec<f64> = (0..150).map(|i| -5.0 - (i % 3) as f64).collect();
+
+        let ratio = calculate_ratio_from_deviations(&deviations, &profile, &config);
+
+        assert!(ratio < 1.0);
+    }
+
+    #[test]
+    fn test_calculate_ratio_neutral_when_balanced() {
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+        let deviations = vec![0.0; 50];
+
+        let ratio = calculate_ratio_from_deviations(&deviations, &profile, &config);
+
+        assert!((ratio - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_detect_sensitivity_detailed_neutral_with_no_glucose_data() {
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+
+        let result = detect_sensitivity_detailed(&profile, &[], &[], &[], Utc::now(), &config).unwrap();
+
+        assert_eq!(result.ratio, 1.0);
+        assert_eq!(result.deviation_count, 0);
+        assert_eq!(result.category, SensitivityCategory::Normal);
+    }
+
+    #[test]
+    fn test_detect_sensitivity_with_config_matches_detailed_ratio() {
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+        let now = Utc::now();
+
+        let detailed = detect_sensitivity_detailed(&profile, &[], &[], &[], now, &config).unwrap();
+        let bare = detect_sensitivity_with_config(&profile, &[], &[], &[], now, &config).unwrap();
+
+        assert_eq!(bare.ratio, detailed.ratio);
+    }
+
+    #[test]
+    fn test_calculate_ratio_clamped_to_config_limits() {
+        let profile = make_autosens_profile();
+        let config = AutosensConfig::default();
+        let deviations: Vec<f64> = (0..150).map(|_| 100.0).collect();
+
+        let ratio = calculate_ratio_from_deviations(&deviations, &profile, &config);
+
+        assert!(ratio <= config.autosens_max);
+    }
 }
@@ -0,0 +1,142 @@
+//! Forward-looking carb absorption / carb-impact modeling
+//!
+//! `cob::calculate` looks backward at glucose deviations to measure carb
+//! absorption that already happened; this module projects a meal's BG
+//! impact *forward* in 5-minute steps so prediction arrays
+//! (`determine_basal::predict`) can add it on top of the insulin effect.
+
+use crate::types::MealData;
+
+const STEPS: usize = 48; // 4 hours at 5-minute resolution
+const DEFAULT_CA_TIME_HOURS: f64 = 4.0;
+/// Extended absorption window used when observed absorption has been slow
+const SLOW_ABSORPTION_CA_TIME_HOURS: f64 = 5.0;
+
+/// Result of projecting a meal's carb impact forward
+#[derive(Debug, Clone, Default)]
+pub struct CarbImpactResult {
+    /// Per-5-minute BG contribution (mg/dL) from carb absorption
+    pub carb_impact: Vec<f64>,
+    /// Carbs still expected to absorb at the end of the projection
+    pub remaining_carbs: f64,
+}
+
+/// Carb sensitivity factor (mg/dL of BG impact per gram of carb)
+pub fn carb_sensitivity_factor(sens: f64, carb_ratio: f64) -> f64 {
+    if carb_ratio <= 0.0 {
+        return 0.0;
+    }
+    sens / carb_ratio
+}
+
+/// Project a meal's carb impact forward in 5-minute steps
+///
+/// Models absorption as linear decay over `remaining_ca_time` hours
+/// (4h by default, extended to 5h when `slow_absorption` is set, e.g. for
+/// higher-fat meals or high-target conditions that have shown slower
+/// observed absorption): `meal_carbimpact (mg/dL/5m) = CSF * carbs /
+/// remaining_ca_time_hours / 60 * 5 * 2`. Carb impact and remaining COB are
+/// both floored at zero once COB is exhausted, and remaining carbs never go
+/// negative.
+pub fn predict_carb_impact(
+    meal: &MealData,
+    sens: f64,
+    carb_ratio: f64,
+    slow_absorption: bool,
+) -> CarbImpactResult {
+    let csf = carb_sensitivity_factor(sens, carb_ratio);
+    let ca_time_hours = if slow_absorption {
+        SLOW_ABSORPTION_CA_TIME_HOURS
+    } else {
+        DEFAULT_CA_TIME_HOURS
+    };
+
+    let carbs = meal.carbs.max(meal.meal_cob).max(0.0);
+    let per_step_impact = if carbs > 0.0 {
+        (csf * carbs / ca_time_hours / 60.0 * 5.0 * 2.0).max(0.0)
+    } else {
+        0.0
+    };
+    let window_steps = ca_time_hours * 60.0 / 5.0;
+
+    let mut remaining = meal.meal_cob.max(0.0);
+    let mut carb_impact = Vec::with_capacity(STEPS);
+
+    for i in 0..STEPS {
+        if remaining <= 0.0 {
+            carb_impact.push(0.0);
+            continue;
+        }
+
+        // Linear decay from twice the window average down to zero at the
+        // end of the absorption window
+        let step_impact = (per_step_impact * (1.0 - i as f64 / window_steps)).max(0.0);
+        let grams = if csf > 0.0 { step_impact / csf } else { 0.0 };
+        carb_impact.push(step_impact);
+        remaining = (remaining - grams).max(0.0);
+    }
+
+    CarbImpactResult {
+        carb_impact,
+        remaining_carbs: remaining,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csf_formula() {
+        // sens=50, carb_ratio=10 -> CSF=5 mg/dL per gram
+        assert!((carb_sensitivity_factor(50.0, 10.0) - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_csf_zero_carb_ratio_is_zero() {
+        assert_eq!(carb_sensitivity_factor(50.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_no_carbs_yields_zero_impact() {
+        let meal = MealData::empty();
+        let result = predict_carb_impact(&meal, 50.0, 10.0, false);
+
+        assert!(result.carb_impact.iter().all(|&v| v == 0.0));
+        assert_eq!(result.remaining_carbs, 0.0);
+    }
+
+    #[test]
+    fn test_carb_impact_exhausts_and_floors_at_zero() {
+        let meal = MealData::with_cob(20.0, 20.0);
+        let result = predict_carb_impact(&meal, 50.0, 10.0, false);
+
+        assert_eq!(result.carb_impact.len(), STEPS);
+        // Early steps should have positive impact
+        assert!(result.carb_impact[0] > 0.0);
+        // By the end of the 4h window, a 20g meal at this CSF should be exhausted
+        assert_eq!(result.remaining_carbs, 0.0);
+        assert_eq!(*result.carb_impact.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_slow_absorption_extends_window() {
+        let meal = MealData::with_cob(40.0, 40.0);
+
+        let normal = predict_carb_impact(&meal, 50.0, 10.0, false);
+        let slow = predict_carb_impact(&meal, 50.0, 10.0, true);
+
+        // Slower absorption spreads the same carbs over a longer window,
+        // so the per-step impact should be smaller
+        assert!(slow.carb_impact[0] < normal.carb_impact[0]);
+    }
+
+    #[test]
+    fn test_remaining_carbs_never_negative() {
+        let meal = MealData::with_cob(5.0, 5.0);
+        let result = predict_carb_impact(&meal, 50.0, 10.0, false);
+
+        assert!(result.remaining_carbs >= 0.0);
+        assert!(result.carb_impact.iter().all(|&v| v >= 0.0));
+    }
+}
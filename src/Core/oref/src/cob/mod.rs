@@ -5,8 +5,8 @@
 
 use chrono::{DateTime, Duration, Utc};
 use crate::types::{COBResult, GlucoseReading, Profile, Treatment, IOBData};
-use crate::insulin::calculate_iob_contrib;
-use crate::profile::{isf_lookup, basal_lookup};
+use crate::insulin::{calculate_iob_contrib, calculate_iob_contrib_continuous};
+use crate::profile::{basal_lookup, carb_ratio_lookup, effective_isf_lookup};
 use crate::Result;
 
 /// Bucketed glucose data point for interpolation
@@ -33,6 +33,8 @@ pub struct CarbAbsorptionResult {
     pub slope_from_min_deviation: f64,
     /// All deviations
     pub all_deviations: Vec<i32>,
+    /// ISF used for the most recent bucket's deviation→carb conversion
+    pub effective_isf: f64,
 }
 
 /// Calculate carb absorption from glucose deviations
@@ -60,9 +62,12 @@ pub fn calculate(
         clock,
     )?;
 
-    // Calculate remaining COB
-    let total_carbs = calculate_total_carbs(treatments, meal_time, clock);
-    let meal_cob = (total_carbs - absorption.carbs_absorbed).max(0.0);
+    // Calculate remaining COB by modeling each carb entry's own absorption
+    // curve and subtracting what it's absorbed by now, rather than
+    // collapsing every entry into one shared `meal_time`. This lets
+    // overlapping meals with different `absorption_time`/`delay` settings
+    // each decay independently.
+    let meal_cob = modeled_remaining_carbs(treatments, clock, profile.max_meal_absorption_time);
 
     Ok(COBResult {
         meal_cob,
@@ -72,6 +77,7 @@ pub fn calculate(
         min_deviation: absorption.min_deviation,
         slope_from_max: absorption.slope_from_max_deviation,
         slope_from_min: absorption.slope_from_min_deviation,
+        effective_isf: absorption.effective_isf,
     })
 }
 
@@ -94,26 +100,78 @@ fn find_meal_time(
         .map(|t| DateTime::from_timestamp_millis(t.effective_date()).unwrap())
 }
 
-/// Calculate total carbs from treatments since meal time
-fn calculate_total_carbs(
+/// Sum of each carb entry's own remaining (unabsorbed) carbs as of `clock`
+///
+/// Unlike the deviation-based `carbs_absorbed` above (which tracks a single
+/// shared `meal_time` window for BG-deviation bucketing), this models each
+/// entry's absorption independently via [`fraction_absorbed`], so
+/// overlapping meals with different `absorption_time`/`delay` settings each
+/// decay on their own schedule instead of collapsing into one meal.
+fn modeled_remaining_carbs(
     treatments: &[Treatment],
-    meal_time: DateTime<Utc>,
     clock: DateTime<Utc>,
+    default_absorption_hours: f64,
 ) -> f64 {
-    let meal_millis = meal_time.timestamp_millis();
     let clock_millis = clock.timestamp_millis();
 
     treatments
         .iter()
-        .filter(|t| {
-            let time = t.effective_date();
-            time >= meal_millis && time <= clock_millis
+        .filter(|t| t.effective_date() <= clock_millis)
+        .filter_map(|t| t.carbs.filter(|&c| c >= 1.0).map(|c| (t, c)))
+        .map(|(t, carbs)| {
+            let minutes_since = (clock_millis - t.effective_date()) as f64 / 60000.0;
+            let delay = t.delay.unwrap_or(0.0);
+            let absorption_time = t.absorption_time.unwrap_or(default_absorption_hours * 60.0);
+            carbs * (1.0 - fraction_absorbed(minutes_since, delay, absorption_time))
         })
-        .filter_map(|t| t.carbs)
-        .filter(|&c| c >= 1.0)
         .sum()
 }
 
+/// Fraction (0.0-1.0) of a carb entry absorbed after `minutes_since` its
+/// entry time, given a `delay` before absorption starts and a total
+/// `absorption_time` window
+///
+/// Models a trapezoidal absorption *rate* curve - zero during `delay`,
+/// ramping linearly up over the first 20% of the window, holding a plateau
+/// through the middle 60%, then ramping linearly down over the final 20% -
+/// whose integral (this function's return value) reaches 1.0 exactly at the
+/// end of the window.
+fn fraction_absorbed(minutes_since: f64, delay: f64, absorption_time: f64) -> f64 {
+    if absorption_time <= 0.0 {
+        return if minutes_since >= delay { 1.0 } else { 0.0 };
+    }
+
+    let t = minutes_since - delay;
+    if t <= 0.0 {
+        return 0.0;
+    }
+    if t >= absorption_time {
+        return 1.0;
+    }
+
+    let ramp = absorption_time * 0.2;
+    let plateau_end = absorption_time * 0.8;
+    // Area of the full trapezoid (ramp-up + plateau + ramp-down), used to
+    // normalize the partial area up to `t` against: two half-ramps plus the
+    // plateau, i.e. (absorption_time - ramp).
+    let total_area = absorption_time - ramp;
+
+    let area = if t <= ramp {
+        // Triangular area under the ramp-up from 0 to t
+        0.5 * (t * t / ramp)
+    } else if t <= plateau_end {
+        // Ramp-up triangle plus the plateau rectangle so far
+        0.5 * ramp + (t - ramp)
+    } else {
+        // Everything before the final ramp-down, plus the ramp-down's area
+        // up to t (full ramp-down triangle minus the remaining sliver)
+        let remaining = absorption_time - t;
+        0.5 * ramp + (plateau_end - ramp) + (0.5 * ramp - 0.5 * (remaining * remaining / ramp))
+    };
+
+    (area / total_area).clamp(0.0, 1.0)
+}
+
 /// Detect carb absorption from BG deviations
 ///
 /// This is the core algorithm that buckets glucose data and calculates
@@ -145,6 +203,7 @@ fn detect_carb_absorption_internal(
     let mut slope_from_max_deviation = 0.0;
     let mut slope_from_min_deviation = 999.0;
     let mut all_deviations = Vec::new();
+    let mut effective_isf = 0.0;
 
     let ci_millis = ci_time.timestamp_millis();
 
@@ -161,9 +220,14 @@ fn detect_carb_absorption_internal(
         let avg_delta = (bg - bucketed_data[i + 3].glucose) / 3.0;
         let delta = bg - bucketed_data[i + 1].glucose;
 
-        // Get sensitivity at this time
+        // Get sensitivity at this time, routed through dynamic ISF (derived
+        // from recent TDD) when the profile opts in via `sens_mode`
         let bg_datetime = DateTime::from_timestamp_millis(bg_time).unwrap_or(ci_time);
-        let sens = isf_lookup(profile, bg_datetime);
+        let sens = effective_isf_lookup(profile, treatments, bg, bg_datetime);
+        // Buckets run newest first, so keep the first ISF computed
+        if effective_isf == 0.0 {
+            effective_isf = sens;
+        }
 
         // Calculate IOB at this time
         let iob = calculate_iob_at_time(profile, treatments, bg_datetime);
@@ -204,8 +268,11 @@ fn detect_carb_absorption_internal(
                 .max(current_deviation / 2.0)
                 .max(profile.min_5m_carbimpact);
 
-            // Convert to carbs absorbed using carb ratio and sensitivity
-            let absorbed = ci * profile.carb_ratio / sens;
+            // Convert to carbs absorbed using the carb ratio scheduled for
+            // this bucket (supports dawn-phenomenon/day-night splits) and
+            // the sensitivity computed above
+            let carb_ratio = carb_ratio_lookup(profile, bg_datetime);
+            let absorbed = ci * carb_ratio / sens;
             carbs_absorbed += absorbed;
         }
     }
@@ -218,6 +285,7 @@ fn detect_carb_absorption_internal(
         slope_from_max_deviation,
         slope_from_min_deviation,
         all_deviations,
+        effective_isf,
     })
 }
 
@@ -301,6 +369,12 @@ fn bucket_glucose_data(
 }
 
 /// Calculate IOB at a specific time
+///
+/// Accounts for both bolus insulin (`treatment.insulin`) and temp basals
+/// (`treatment.rate` + `treatment.duration`): a temp basal is net of the
+/// scheduled basal it displaces, delivered continuously rather than as a
+/// point dose, so it's run through [`calculate_iob_contrib_continuous`]
+/// instead of being skipped.
 fn calculate_iob_at_time(
     profile: &Profile,
     treatments: &[Treatment],
@@ -311,6 +385,8 @@ fn calculate_iob_at_time(
 
     let mut iob = 0.0;
     let mut activity = 0.0;
+    let mut basal_iob = 0.0;
+    let mut bolus_iob = 0.0;
 
     for treatment in treatments {
         let treatment_time = treatment.effective_date();
@@ -325,30 +401,61 @@ fn calculate_iob_at_time(
             continue;
         }
 
-        // Get insulin amount
-        let insulin = treatment.insulin.unwrap_or(0.0);
-        if insulin <= 0.0 {
+        let mins_ago = (time_millis - treatment_time) as f64 / 60000.0;
+
+        if let Some(insulin) = treatment.insulin {
+            if insulin > 0.0 {
+                let contrib = calculate_iob_contrib(
+                    insulin,
+                    mins_ago,
+                    profile.curve,
+                    profile.dia,
+                    profile.peak,
+                );
+
+                iob += contrib.iob_contrib;
+                activity += contrib.activity_contrib;
+                bolus_iob += contrib.iob_contrib;
+            }
             continue;
         }
 
-        let minutes_ago = (time_millis - treatment_time) as f64 / 60000.0;
-        let contrib = calculate_iob_contrib(
-            insulin,
-            minutes_ago,
-            profile.curve,
-            profile.dia,
-            profile.peak,
-        );
-
-        iob += contrib.iob_contrib;
-        activity += contrib.activity_contrib;
+        if let (Some(rate), Some(duration)) = (treatment.rate, treatment.duration) {
+            if duration <= 0.0 {
+                continue;
+            }
+
+            let treatment_datetime = DateTime::from_timestamp_millis(treatment_time).unwrap_or(time);
+            let scheduled_basal = basal_lookup(profile, treatment_datetime);
+            let net_rate = rate - scheduled_basal;
+            let net_insulin = net_rate * duration / 60.0;
+
+            if net_insulin.abs() < 0.0001 {
+                continue;
+            }
+
+            let end_mins_ago = (mins_ago - duration).max(0.0);
+            let contrib = calculate_iob_contrib_continuous(
+                net_insulin.abs(),
+                mins_ago,
+                end_mins_ago,
+                profile.curve,
+                profile.dia,
+                profile.peak,
+            );
+
+            let sign = if net_insulin < 0.0 { -1.0 } else { 1.0 };
+            iob += contrib.iob_contrib * sign;
+            activity += contrib.activity_contrib * sign;
+            basal_iob += contrib.iob_contrib * sign;
+        }
     }
 
     IOBData {
         iob,
         activity,
-        basal_iob: 0.0,
-        bolus_iob: iob,
+        basal_iob,
+        bolus_iob,
         net_basal_insulin: 0.0,
         bolus_insulin: 0.0,
         time,
@@ -416,4 +523,111 @@ mod tests {
         assert_eq!(round_to_decimal(1.2355, 2), 1.24);
         assert_eq!(round_to_decimal(-1.2345, 2), -1.23);
     }
+
+    #[test]
+    fn test_effective_isf_matches_static_by_default() {
+        let profile = test_profile();
+        let now = Utc::now();
+        let meal_time = now - Duration::minutes(20);
+
+        let glucose_data: Vec<GlucoseReading> = (0..5)
+            .map(|i| GlucoseReading::new(100.0 + i as f64 * 10.0, (meal_time + Duration::minutes(i * 5)).timestamp_millis()))
+            .collect();
+        let treatments = vec![Treatment::carbs(30.0, meal_time)];
+
+        let result = calculate(&profile, &glucose_data, &treatments, now).unwrap();
+
+        // sens_mode defaults to Static, so the ISF used for deviation->carb
+        // conversion should match the plain schedule value
+        assert!((result.effective_isf - profile.sens).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_calculate_iob_at_time_includes_temp_basal() {
+        let profile = test_profile();
+        let now = Utc::now();
+
+        // Temp of 2 U/hr for 30 min vs a 1 U/hr scheduled basal (default,
+        // since test_profile() has an empty basal_profile) started 15 min
+        // ago - half delivered, net 1 U/hr above schedule
+        let treatments = vec![Treatment::temp_basal(2.0, 30.0, now - Duration::minutes(15))];
+
+        let iob = calculate_iob_at_time(&profile, &treatments, now);
+
+        assert!(iob.basal_iob > 0.0);
+        assert!(iob.bolus_iob.abs() < 0.0001);
+        assert!((iob.iob - iob.basal_iob).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_calculate_iob_at_time_ignores_temp_basal_matching_schedule() {
+        let profile = test_profile();
+        let now = Utc::now();
+
+        // Temp at exactly the scheduled (current_basal) rate displaces no
+        // net insulin, so it shouldn't contribute any IOB
+        let treatments = vec![Treatment::temp_basal(profile.current_basal, 30.0, now - Duration::minutes(15))];
+
+        let iob = calculate_iob_at_time(&profile, &treatments, now);
+
+        assert!(iob.iob.abs() < 0.0001);
+        assert!(iob.basal_iob.abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_fraction_absorbed_zero_during_delay() {
+        assert_eq!(fraction_absorbed(10.0, 20.0, 180.0), 0.0);
+    }
+
+    #[test]
+    fn test_fraction_absorbed_complete_by_end_of_window() {
+        assert!((fraction_absorbed(180.0, 0.0, 180.0) - 1.0).abs() < 0.0001);
+        assert_eq!(fraction_absorbed(200.0, 0.0, 180.0), 1.0);
+    }
+
+    #[test]
+    fn test_fraction_absorbed_monotonically_increasing() {
+        let mut last = 0.0;
+        for mins in (0..200).step_by(10) {
+            let f = fraction_absorbed(mins as f64, 0.0, 180.0);
+            assert!(f >= last);
+            last = f;
+        }
+    }
+
+    #[test]
+    fn test_modeled_remaining_carbs_decays_over_time() {
+        let now = Utc::now();
+        let entry_time = now - Duration::minutes(90);
+        let treatments = vec![Treatment {
+            absorption_time: Some(180.0),
+            ..Treatment::carbs(40.0, entry_time)
+        }];
+
+        let remaining = modeled_remaining_carbs(&treatments, now, 6.0);
+        assert!(remaining > 0.0 && remaining < 40.0);
+    }
+
+    #[test]
+    fn test_modeled_remaining_carbs_supports_overlapping_meals() {
+        let now = Utc::now();
+        // An old, nearly-finished meal plus a fresh one started just now -
+        // each should decay on its own window rather than collapsing
+        let treatments = vec![
+            Treatment {
+                absorption_time: Some(60.0),
+                ..Treatment::carbs(20.0, now - Duration::minutes(55))
+            },
+            Treatment {
+                absorption_time: Some(180.0),
+                ..Treatment::carbs(30.0, now)
+            },
+        ];
+
+        let remaining = modeled_remaining_carbs(&treatments, now, 6.0);
+        // The fresh 30g entry is barely absorbed; the 55-min-old 20g entry
+        // (60 min window) is nearly done, so the total should sit close to
+        // the fresh entry alone but above it
+        assert!(remaining > 28.0 && remaining < 31.0);
+    }
 }
@@ -2,14 +2,75 @@
 
 use chrono::Utc;
 use crate::types::{
-    DetermineBasalResult, GlucoseStatus,
-    IOBData, MealData, Profile,
+    CurrentTemp, DetermineBasalResult, GlucoseStatus,
+    IOBData, MealData, Profile, TempBasalState,
 };
+use crate::profile::effective_isf_lookup;
+use crate::profile::dynamic_isf::TDDInputs;
+use crate::profile::targets::{bg_targets_lookup, apply_sensitivity_ratio};
+use crate::tdd::calculate_tdd;
+use crate::temp_basal::set_temp_basal;
 use crate::utils::round_basal;
+use crate::utils::format_bg;
 use crate::Result;
 use super::DetermineBasalInputs;
 use super::predictions;
 use super::smb;
+use super::InsulinSensitivityMode;
+
+/// How far into the prediction curves (in 5-minute steps) minGuardBG looks
+/// when deciding whether a near-term low overrides a high eventual BG
+const GUARD_HORIZON_STEPS: usize = 9; // 45 minutes
+
+/// Below this, a predicted near-term low blocks raising basal even if
+/// `eventual_bg` is above target
+const MIN_GUARD_BG_THRESHOLD: f64 = 80.0;
+
+/// Lowest BG reached across the given prediction curves within the near-term
+/// guard horizon (oref1's minGuardBG / minZTGuardBG)
+fn min_guard_bg(curves: &[&[f64]]) -> f64 {
+    curves
+        .iter()
+        .flat_map(|curve| curve.iter().take(GUARD_HORIZON_STEPS))
+        .cloned()
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Below this, a BG reading is treated as a sensor calibrating rather than a
+/// real low
+const MIN_PLAUSIBLE_BG: f64 = 39.0;
+/// BG readings older than this are too stale to dose against
+const MAX_BG_STALENESS_MINUTES: f64 = 12.0;
+/// Nightscout noise level (1 clean - 4 heavy, 5 unknown) at or above which
+/// the sensor is too unreliable to dose against
+const HIGH_NOISE_THRESHOLD: f64 = 3.0;
+
+/// Build a safe temp-zero result for a pre-dose validation failure
+fn pre_dose_hold(
+    meal_data: &MealData,
+    iob_data: &IOBData,
+    bg: f64,
+    bg_mins_ago: f64,
+    reason: String,
+) -> DetermineBasalResult {
+    let mut result = DetermineBasalResult::temp_basal(0.0, 30, reason);
+    result.cob = meal_data.meal_cob;
+    result.iob = iob_data.iob;
+    result.eventual_bg = bg;
+    result.bg_mins_ago = Some(bg_mins_ago);
+    result
+}
+
+/// Adapt the caller's `CurrentTemp` into the `TempBasalState` the safety
+/// layer expects, so an inactive temp reads as "nothing running" rather than
+/// a zero-rate temp that could itself get suppressed against.
+fn current_temp_state(current_temp: &CurrentTemp) -> Option<TempBasalState> {
+    if !current_temp.is_active() {
+        return None;
+    }
+
+    Some(TempBasalState::new(0, current_temp.duration, Some(current_temp.rate)))
+}
 
 /// Run the determine basal algorithm
 ///
@@ -22,6 +83,8 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
         profile,
         autosens_data,
         meal_data,
+        treatments,
+        pending_insulin,
         micro_bolus_allowed,
         current_time,
     } = inputs;
@@ -34,17 +97,82 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
     }
 
     let bg = glucose_status.glucose;
-    let target_bg = profile.min_bg;
-    let sens = profile.sens;
-    let basal = round_basal(profile.current_basal, profile);
-
-    // Check if BG is too old
     let bg_mins_ago = (now.timestamp_millis() - glucose_status.date) as f64 / 60000.0;
 
+    // ============ Pre-Dose Validation ============
+    // Don't act on a reading that can't be trusted yet: an implausibly low
+    // BG usually means the sensor is calibrating, a stale reading means the
+    // loop hasn't actually seen what's happening now, and a noisy sensor is
+    // reporting values unreliable enough to dose against
+    if bg < MIN_PLAUSIBLE_BG {
+        return Ok(pre_dose_hold(
+            meal_data,
+            iob_data,
+            bg,
+            bg_mins_ago,
+            "CGM is calibrating or in ??? state".to_string(),
+        ));
+    }
+
+    if bg_mins_ago > MAX_BG_STALENESS_MINUTES {
+        return Ok(pre_dose_hold(
+            meal_data,
+            iob_data,
+            bg,
+            bg_mins_ago,
+            format!("BG data is {:.0}m old, too stale to dose on", bg_mins_ago),
+        ));
+    }
+
+    if let Some(noise) = glucose_status.noise {
+        if noise >= HIGH_NOISE_THRESHOLD {
+            return Ok(pre_dose_hold(
+                meal_data,
+                iob_data,
+                bg,
+                bg_mins_ago,
+                format!("CGM noise {:.0} too high to trust", noise),
+            ));
+        }
+    }
+
+    let ratio = autosens_data.ratio;
+    let targets = apply_sensitivity_ratio(bg_targets_lookup(profile, now), ratio);
+    let min_bg = targets.min_bg;
+    let max_bg = targets.max_bg;
+    let target_bg = min_bg;
+    // Resistant (ratio > 1) raises the basal rate but lowers the ISF number
+    // (more insulin per correction); sensitive (ratio < 1) does the
+    // opposite, consistent with the target shift above
+    let sens = effective_isf_lookup(profile, treatments, bg, now) / ratio;
+    let basal = round_basal(profile.current_basal * ratio, profile);
+
+    // Surface the autosens adjustment whenever it actually moves the basal,
+    // so an adaptive change away from the schedule is observable in `reason`
+    let autosens_note = if ratio != 1.0 {
+        format!(
+            " [Autosens adjusting basal from {:.3} to {:.3}]",
+            profile.current_basal, basal
+        )
+    } else {
+        String::new()
+    };
+
+    // When dynamic ISF is enabled, surface the TDD that drove `sens` so the
+    // reason string explains where a non-schedule sensitivity came from
+    let tdd_result = (profile.sens_mode == InsulinSensitivityMode::Dynamic)
+        .then(|| calculate_tdd(treatments, now.timestamp_millis()));
+    let dynamic_isf_note = tdd_result
+        .map(|tdd| format!(" [dynamic ISF {:.1} from TDD {:.1}U]", sens, tdd.tdd))
+        .unwrap_or_default();
+    // `effective_isf_lookup` re-blends TDD internally, so feed it through
+    // already-blended to `predict_glucose` instead of re-deriving the blend
+    let tdd_inputs = tdd_result.map(|tdd| TDDInputs::new(tdd.tdd, tdd.tdd));
+
     // Generate all prediction curves
-    let pred_bgs = predictions::predict_glucose(glucose_status, iob_data, profile);
-    let pred_bgs_iob = generate_iob_only_predictions(glucose_status, iob_data, profile);
-    let pred_bgs_zt = generate_zero_temp_predictions(glucose_status, iob_data, profile);
+    let pred_bgs = predictions::predict_glucose(glucose_status, iob_data, profile, tdd_inputs.as_ref());
+    let pred_bgs_iob = generate_iob_only_predictions(glucose_status, iob_data, sens);
+    let pred_bgs_zt = predictions::predict_glucose_zero_temp(glucose_status, iob_data, profile, sens);
     let pred_bgs_uam = generate_uam_predictions(glucose_status, iob_data, profile);
     let pred_bgs_cob = generate_cob_predictions(glucose_status, iob_data, meal_data, profile);
 
@@ -52,8 +180,8 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
     if bg < 80.0 {
         // Low glucose - suspend insulin
         let reason = format!(
-            "BG {:.0} < 80, temp zero",
-            bg
+            "BG {} < {}, temp zero",
+            format_bg(bg, profile.out_units), format_bg(80.0, profile.out_units)
         );
 
         let mut result = DetermineBasalResult::temp_basal(0.0, 30, reason);
@@ -77,7 +205,7 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
 
     // ============ Calculate Eventual BG ============
     // Eventual BG if we continue at current temp and let IOB decay
-    let eventual_bg = predictions::calculate_eventual_bg(glucose_status, iob_data, profile);
+    let eventual_bg = predictions::calculate_eventual_bg_with_sens(glucose_status, iob_data, sens);
 
     // ============ Determine Action ============
     let mut result = DetermineBasalResult::default();
@@ -87,6 +215,8 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
     result.bg_mins_ago = Some(bg_mins_ago);
     result.target_bg = Some(target_bg);
     result.sensitivity_ratio = Some(autosens_data.ratio);
+    let min_guard = min_guard_bg(&[&pred_bgs_iob, &pred_bgs_zt]);
+    result.min_guard_bg = Some(min_guard);
 
     // Always populate predictions
     result.predicted_bg = Some(pred_bgs);
@@ -100,79 +230,134 @@ pub fn determine_basal(inputs: &DetermineBasalInputs) -> Result<DetermineBasalRe
     result.insulin_req = Some(insulin_req);
 
     // ============ In Range - No Action Needed ============
-    if eventual_bg >= profile.min_bg && eventual_bg <= profile.max_bg {
+    if eventual_bg >= min_bg && eventual_bg <= max_bg {
         // In range - check if we need to cancel high temp
         if current_temp.is_active() && current_temp.rate > basal {
-            // Cancel high temp
-            result.rate = Some(basal);
-            result.duration = Some(30);
+            // Cancel high temp - routed through set_temp_basal so a running
+            // temp already close enough to `basal` is left alone instead of
+            // being churned
+            let temp = current_temp_state(current_temp);
+            let rec = set_temp_basal(basal, 30.0, profile, temp.as_ref());
+
             result.reason = format!(
-                "Eventual BG {:.0} in range ({:.0}-{:.0}), canceling high temp",
-                eventual_bg, profile.min_bg, profile.max_bg
+                "Eventual BG {} in range ({}-{}), canceling high temp; {}{}{}",
+                format_bg(eventual_bg, profile.out_units),
+                format_bg(min_bg, profile.out_units),
+                format_bg(max_bg, profile.out_units),
+                rec.reason, dynamic_isf_note, autosens_note
             );
+
+            if rec.should_set {
+                result.rate = Some(rec.rate);
+                result.duration = Some(rec.duration.round() as i64);
+            }
         } else {
             result.reason = format!(
-                "Eventual BG {:.0} in range ({:.0}-{:.0}), no action needed",
-                eventual_bg, profile.min_bg, profile.max_bg
+                "Eventual BG {} in range ({}-{}), no action needed{}",
+                format_bg(eventual_bg, profile.out_units),
+                format_bg(min_bg, profile.out_units),
+                format_bg(max_bg, profile.out_units),
+                format!("{}{}", dynamic_isf_note, autosens_note)
             );
         }
         return Ok(result);
     }
 
     // ============ Above Target ============
-    if eventual_bg > profile.max_bg {
+    if eventual_bg > max_bg {
+        // A near-term curve (IOB-only or zero-temp) predicts a low even
+        // though the single-point eventual BG is high; hold rather than
+        // raise basal into a drop we can already see coming
+        if min_guard < MIN_GUARD_BG_THRESHOLD {
+            let temp = current_temp_state(current_temp);
+            let rec = set_temp_basal(0.0, 30.0, profile, temp.as_ref());
+
+            result.reason = format!(
+                "Eventual BG {} > {} but predicted min {} < {} guard, holding basal; {}{}{}",
+                format_bg(eventual_bg, profile.out_units),
+                format_bg(max_bg, profile.out_units),
+                format_bg(min_guard, profile.out_units),
+                format_bg(MIN_GUARD_BG_THRESHOLD, profile.out_units),
+                rec.reason, dynamic_isf_note, autosens_note
+            );
+
+            if rec.should_set {
+                result.rate = Some(rec.rate);
+                result.duration = Some(rec.duration.round() as i64);
+            }
+
+            return Ok(result);
+        }
+
         // Need more insulin
         let needed_rate = basal + (insulin_req / 0.5); // Rough conversion
-        let needed_rate = needed_rate.max(0.0).min(profile.max_basal);
-        let needed_rate = round_basal(needed_rate, profile);
 
-        // Check if SMB would help
-        if *micro_bolus_allowed && insulin_req > 0.0 {
-            if let Some(smb_amount) = smb::calculate_smb(profile, insulin_req, iob_data.iob, meal_data.meal_cob, basal) {
+        // Check if SMB would help, discounting insulin already committed but
+        // not yet reflected in IOB so an in-flight temp/bolus isn't double-dosed
+        let smb_insulin_req = (insulin_req - *pending_insulin).max(0.0);
+        if *micro_bolus_allowed && smb_insulin_req > 0.0 {
+            if let Some(smb_amount) = smb::calculate_smb(profile, smb_insulin_req, iob_data.iob, meal_data.meal_cob, basal) {
                 result.units = Some(smb_amount);
             }
         }
 
-        result.rate = Some(needed_rate);
-        result.duration = Some(30);
+        let temp = current_temp_state(current_temp);
+        let rec = set_temp_basal(needed_rate, 30.0, profile, temp.as_ref());
+
         result.reason = format!(
-            "Eventual BG {:.0} > {:.0}, insulin required {:.2}U, setting temp {:.2}U/hr",
-            eventual_bg, profile.max_bg, insulin_req, needed_rate
+            "Eventual BG {} > {}, insulin required {:.2}U; {}{}{}",
+            format_bg(eventual_bg, profile.out_units),
+            format_bg(max_bg, profile.out_units),
+            insulin_req, rec.reason, dynamic_isf_note, autosens_note
         );
 
+        if rec.should_set {
+            result.rate = Some(rec.rate);
+            result.duration = Some(rec.duration.round() as i64);
+        }
+
         return Ok(result);
     }
 
     // ============ Below Target ============
-    if eventual_bg < profile.min_bg {
+    if eventual_bg < min_bg {
         // Reduce insulin
         let needed_rate = basal + (insulin_req / 0.5);
-        let needed_rate = needed_rate.max(0.0);
-        let needed_rate = round_basal(needed_rate, profile);
 
-        result.rate = Some(needed_rate);
-        result.duration = Some(30);
+        let temp = current_temp_state(current_temp);
+        let rec = set_temp_basal(needed_rate, 30.0, profile, temp.as_ref());
+
         result.reason = format!(
-            "Eventual BG {:.0} < {:.0}, reducing to {:.2}U/hr",
-            eventual_bg, profile.min_bg, needed_rate
+            "Eventual BG {} < {}; {}{}{}",
+            format_bg(eventual_bg, profile.out_units),
+            format_bg(min_bg, profile.out_units),
+            rec.reason, dynamic_isf_note, autosens_note
         );
 
+        if rec.should_set {
+            result.rate = Some(rec.rate);
+            result.duration = Some(rec.duration.round() as i64);
+        }
+
         return Ok(result);
     }
 
     // Default: no action
-    result.reason = "No action needed".to_string();
+    result.reason = format!("No action needed{}{}", dynamic_isf_note, autosens_note);
     Ok(result)
 }
 
 /// Generate IOB-only predictions (no delta extrapolation)
+///
+/// `sens` is the caller's effective ISF (static or autosens/dynamic-adjusted)
+/// so this safety-guard curve drops by the same amount per unit of IOB as
+/// the dose calculation actually assumes.
 fn generate_iob_only_predictions(
     glucose_status: &GlucoseStatus,
     iob_data: &IOBData,
-    profile: &Profile,
+    sens: f64,
 ) -> Vec<f64> {
     let bg = glucose_status.glucose;
-    let sens = profile.sens;
 
     (0..48).map(|i| {
         let minutes = i as f64 * 5.0;
@@ -182,30 +367,6 @@ fn generate_iob_only_predictions(
     }).collect()
 }
 
-/// Generate zero-temp predictions (no insulin delivery)
-fn generate_zero_temp_predictions(
-    glucose_status: &GlucoseStatus,
-    iob_data: &IOBData,
-    profile: &Profile,
-) -> Vec<f64> {
-    let bg = glucose_status.glucose;
-    let sens = profile.sens;
-    let basal = profile.current_basal;
-
-    (0..48).map(|i| {
-        let minutes = i as f64 * 5.0;
-        // IOB effect decays, but we're not adding new insulin
-        let iob_factor = (-minutes / 60.0).exp();
-        let predicted_iob_effect = iob_data.iob * iob_factor * sens;
-        // Add delta extrapolation (BG rises if we stop insulin)
-        let delta_factor = (-minutes / 45.0).exp();
-        let delta_effect = glucose_status.delta.max(0.0) * (minutes / 5.0) * delta_factor;
-        // Baseline BG rise from lack of basal
-        let basal_rise = (basal / 60.0) * minutes * sens * 0.5;
-        (bg + delta_effect + basal_rise - predicted_iob_effect).max(39.0)
-    }).collect()
-}
-
 /// Generate UAM predictions (unannounced meal detection)
 fn generate_uam_predictions(
     glucose_status: &GlucoseStatus,
@@ -283,6 +444,9 @@ mod tests {
         let profile = Profile {
             current_basal: 0.9,
             max_basal: 3.5,
+            max_daily_basal: 0.9,
+            max_daily_safety_multiplier: 3.0,
+            current_basal_safety_multiplier: 4.0,
             min_bg: 110.0,
             max_bg: 120.0,
             sens: 40.0,
@@ -307,6 +471,8 @@ mod tests {
             profile: &profile,
             autosens_data: &autosens,
             meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
             micro_bolus_allowed: false,
             current_time: Some(Utc::now()),
         };
@@ -317,6 +483,159 @@ mod tests {
         assert!(result.rate.is_none() || result.rate == Some(profile.current_basal));
     }
 
+    #[test]
+    fn test_reason_formats_bg_in_mmol_when_profile_requests_it() {
+        let (glucose_status, current_temp, iob_data, mut profile, autosens, meal_data) = make_inputs();
+        profile.out_units = crate::utils::BgUnits::MmolL;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        // min_bg 110 mg/dL -> 6.1 mmol/L, max_bg 120 mg/dL -> 6.7 mmol/L
+        assert!(result.reason.contains("6.1"));
+        assert!(result.reason.contains("6.7"));
+        assert!(!result.reason.contains("110"));
+    }
+
+    #[test]
+    fn test_in_range_cancels_distant_high_temp() {
+        let (glucose_status, _current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        let basal = round_basal(profile.current_basal, &profile);
+        // Far above basal and nearly expired, so it should be canceled
+        // outright rather than suppressed as "close enough"
+        let current_temp = CurrentTemp::absolute(basal * 3.0, 5.0);
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert_eq!(result.rate, Some(basal));
+        assert!(result.reason.contains("canceling high temp"));
+    }
+
+    #[test]
+    fn test_in_range_suppresses_near_identical_high_temp() {
+        let (glucose_status, _current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        let basal = round_basal(profile.current_basal, &profile);
+        // Within 20% of basal with plenty of time left: leave it running
+        let running_rate = basal * 1.1;
+        let current_temp = CurrentTemp::absolute(running_rate, 25.0);
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert!(result.rate.is_none());
+        assert!(result.reason.contains("no change"));
+    }
+
+    #[test]
+    fn test_implausibly_low_bg_is_treated_as_calibrating() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 20.0;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert_eq!(result.rate, Some(0.0));
+        assert!(result.reason.contains("calibrating"));
+    }
+
+    #[test]
+    fn test_stale_bg_blocks_dosing() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        let now = Utc::now();
+        glucose_status.date = (now - chrono::Duration::minutes(20)).timestamp_millis();
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(now),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert_eq!(result.rate, Some(0.0));
+        assert!(result.reason.contains("stale"));
+    }
+
+    #[test]
+    fn test_high_noise_blocks_dosing() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.noise = Some(4.0);
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert_eq!(result.rate, Some(0.0));
+        assert!(result.reason.contains("noise"));
+    }
+
     #[test]
     fn test_low_glucose_suspend() {
         let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
@@ -329,6 +648,8 @@ mod tests {
             profile: &profile,
             autosens_data: &autosens,
             meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
             micro_bolus_allowed: false,
             current_time: Some(Utc::now()),
         };
@@ -352,6 +673,8 @@ mod tests {
             profile: &profile,
             autosens_data: &autosens,
             meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
             micro_bolus_allowed: false,
             current_time: Some(Utc::now()),
         };
@@ -361,4 +684,262 @@ mod tests {
         // Should increase basal
         assert!(result.rate.unwrap() > profile.current_basal);
     }
+
+    #[test]
+    fn test_above_target_smb_fires_with_no_pending_insulin() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 180.0;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: true,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert!(result.units.is_some());
+    }
+
+    #[test]
+    fn test_pending_insulin_suppresses_smb_to_avoid_double_dosing() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 180.0;
+
+        // Same scenario as above, but a running temp/unconfirmed bolus has
+        // already committed insulin that hasn't shown up in IOB yet - that
+        // should be enough on its own to cover the correction, so no
+        // additional SMB should be recommended on top of it
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 10.0,
+            micro_bolus_allowed: true,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert!(result.units.is_none());
+    }
+
+    #[test]
+    fn test_predicted_near_term_low_blocks_raising_basal() {
+        let (mut glucose_status, current_temp, mut iob_data, profile, autosens, meal_data) = make_inputs();
+        // BG is rising fast right now (eventual_bg lands above target), but
+        // existing IOB alone would drop it well below the guard threshold
+        // once the trend stalls
+        glucose_status.glucose = 150.0;
+        glucose_status.delta = 10.0;
+        iob_data.iob = 3.0;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert!(result.eventual_bg > profile.max_bg);
+        assert!(result.min_guard_bg.unwrap() < 80.0);
+        assert_eq!(result.rate, Some(0.0));
+        assert!(result.reason.contains("guard"));
+    }
+
+    #[test]
+    fn test_high_glucose_rate_never_exceeds_safe_ceiling() {
+        let (mut glucose_status, current_temp, iob_data, mut profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 400.0;
+        // Tighten the ceiling well below max_basal so the request would
+        // otherwise blow past it
+        profile.max_daily_basal = 0.5;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        let max_safe = crate::temp_basal::get_max_safe_basal(&profile);
+        assert!(result.rate.unwrap() <= max_safe + 0.001);
+    }
+
+    #[test]
+    fn test_high_glucose_rate_respects_current_basal_safety_multiplier() {
+        let (mut glucose_status, current_temp, iob_data, mut profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 400.0;
+        // Tighten via the current-basal multiplier specifically, leaving
+        // max_basal and max_daily_basal loose so this is the binding limit
+        profile.max_basal = 10.0;
+        profile.max_daily_basal = 10.0;
+        profile.current_basal_safety_multiplier = 1.5;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        let max_safe = crate::temp_basal::get_max_safe_basal(&profile);
+        assert!((max_safe - profile.current_basal * 1.5).abs() < 0.001);
+        assert!(result.rate.unwrap() <= max_safe + 0.001);
+        assert!(result.reason.contains("max safe basal"));
+    }
+
+    #[test]
+    fn test_above_target_suppresses_near_identical_running_temp() {
+        let (mut glucose_status, _current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 180.0;
+
+        // A temp already set to exactly what we'd otherwise request (after
+        // the max-safe-basal cap), with plenty of time left, should be left
+        // alone rather than re-set
+        let basal = round_basal(profile.current_basal, &profile);
+        let insulin_req = (180.0 - profile.min_bg) / profile.sens;
+        let max_safe = crate::temp_basal::get_max_safe_basal(&profile);
+        let expected_rate = round_basal((basal + insulin_req / 0.5).min(max_safe), &profile);
+        let current_temp = CurrentTemp::absolute(expected_rate, 25.0);
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        // Suppressed: no new temp is issued, leaving the running one alone
+        assert!(result.rate.is_none());
+        assert!(result.reason.contains("no change"));
+    }
+
+    #[test]
+    fn test_resistant_autosens_ratio_shifts_target_and_raises_basal() {
+        let (mut glucose_status, mut current_temp, iob_data, profile, _autosens, meal_data) = make_inputs();
+        // Shift target down by (1.2 - 1.0) * 40 = 8, to 102-112
+        let autosens = AutosensData::with_ratio(1.2);
+        glucose_status.glucose = 107.0;
+        // A running temp above the *unscaled* basal (0.9) but below the
+        // ratio-scaled basal (~1.08) should be left alone, not canceled
+        current_temp.rate = 0.95;
+        current_temp.duration = 20.0;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert_eq!(result.target_bg, Some(102.0));
+        assert!(result.reason.contains("102"));
+        assert!(result.reason.contains("112"));
+        assert!(result.reason.contains("no action needed"));
+        assert!(result.reason.contains("Autosens adjusting basal from 0.900 to 1.100"));
+    }
+
+    #[test]
+    fn test_dynamic_isf_mode_surfaces_tdd_in_reason() {
+        let (mut glucose_status, current_temp, iob_data, mut profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 180.0;
+        profile.sens_mode = InsulinSensitivityMode::Dynamic;
+
+        let now = Utc::now();
+        let treatments = vec![crate::types::Treatment::bolus(10.0, now - chrono::Duration::hours(2))];
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &treatments,
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(now),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        // The reason string should explain where a non-schedule sensitivity came from
+        assert!(result.reason.contains("dynamic ISF"));
+        assert!(result.reason.contains("TDD"));
+    }
+
+    #[test]
+    fn test_static_mode_omits_dynamic_isf_note() {
+        let (mut glucose_status, current_temp, iob_data, profile, autosens, meal_data) = make_inputs();
+        glucose_status.glucose = 180.0;
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: false,
+            current_time: Some(Utc::now()),
+        };
+
+        let result = determine_basal(&inputs).unwrap();
+
+        assert!(!result.reason.contains("dynamic ISF"));
+    }
 }
@@ -5,14 +5,16 @@
 mod algorithm;
 mod smb;
 mod predictions;
+mod predict;
 
 pub use algorithm::determine_basal;
 pub use smb::should_enable_smb;
-pub use predictions::predict_glucose;
+pub use predictions::{predict_glucose, InsulinSensitivityMode};
+pub use predict::{predict_arrays, GlucosePredictions};
 
 use crate::types::{
     AutosensData, CurrentTemp, GlucoseStatus,
-    IOBData, MealData, Profile,
+    IOBData, MealData, Profile, Treatment,
 };
 
 /// Inputs for the determine basal algorithm
@@ -35,6 +37,16 @@ pub struct DetermineBasalInputs<'a> {
     /// Meal data
     pub meal_data: &'a MealData,
 
+    /// Treatment history, used to derive dynamic ISF from TDD when
+    /// `profile.sens_mode == InsulinSensitivityMode::Dynamic`
+    pub treatments: &'a [Treatment],
+
+    /// Insulin already committed but not yet reflected in IOB - the net
+    /// basal a running temp will still deliver plus any unconfirmed bolus
+    /// (see [`crate::iob::get_pending_insulin`]) - discounted from the SMB
+    /// recommendation so an in-flight delivery isn't double-dosed
+    pub pending_insulin: f64,
+
     /// Whether micro bolus is allowed
     pub micro_bolus_allowed: bool,
 
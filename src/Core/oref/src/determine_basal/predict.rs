@@ -0,0 +1,264 @@
+//! Glucose prediction arrays (DOC 9/10)
+//!
+//! `predictions::predict_glucose` produces a single flat curve; real
+//! closed-loop dosing needs several parallel trajectories so the decision
+//! layer can reason about worst/best case. This assembles four 5-minute-step
+//! arrays over a fixed 4-hour horizon — IOBpredBG, ZTpredBG, COBpredBG and
+//! UAMpredBG — using the DOC 9 expected-delta step: `expected_delta =
+//! round(bgi + (target_bg - eventual_bg) / dia_in_5min_blocks, 1)`.
+
+use super::predictions::{calculate_bgi, calculate_eventual_bg};
+use crate::types::{GlucoseStatus, IOBData, MealData, Profile};
+
+const STEPS: usize = 48; // 4 hours at 5-minute resolution
+
+/// The four DOC 9/10 prediction arrays plus the summary fields dosing
+/// decisions key off of.
+#[derive(Debug, Clone, Default)]
+pub struct GlucosePredictions {
+    /// Projected BG assuming current IOB decays on schedule
+    pub iob_pred_bg: Vec<f64>,
+    /// Projected BG assuming all basal delivery stops now (zero temp)
+    pub zt_pred_bg: Vec<f64>,
+    /// Projected BG with carb absorption added on top of the IOB effect
+    pub cob_pred_bg: Vec<f64>,
+    /// Projected BG assuming an unannounced meal (recent deviation persists)
+    pub uam_pred_bg: Vec<f64>,
+    /// Where BG is headed once all current IOB has acted
+    pub eventual_bg: f64,
+    /// Lowest point across the IOB/COB/UAM arrays
+    pub min_pred_bg: f64,
+    /// Highest point across the IOB/COB/UAM arrays
+    pub max_pred_bg: f64,
+    /// Lowest point of the zero-temp array (the "worst case if we suspend now" guard)
+    pub min_zt_guard_bg: f64,
+}
+
+/// Number of 5-minute blocks spanning half the DIA (DOC 9 `dia_in_5min_blocks`)
+fn dia_in_5min_blocks(dia_hours: f64) -> f64 {
+    (dia_hours / 2.0 * 60.0) / 5.0
+}
+
+/// Walk a prediction array forward one 5-minute step at a time using the
+/// DOC 9 expected-delta formula, with a caller-supplied extra BG contribution
+/// (carb absorption, persisted deviation, ...) added at each step.
+fn project(
+    start_bg: f64,
+    target_bg: f64,
+    eventual_bg: f64,
+    dia_blocks: f64,
+    sens: f64,
+    mut activity_at: impl FnMut(usize) -> f64,
+    mut extra_at: impl FnMut(usize) -> f64,
+) -> Vec<f64> {
+    let mut bg = start_bg;
+    let mut out = Vec::with_capacity(STEPS);
+    out.push(bg.max(39.0));
+
+    for i in 0..STEPS - 1 {
+        let bgi = calculate_bgi(activity_at(i), sens);
+        let expected_delta = ((bgi + (target_bg - eventual_bg) / dia_blocks) * 10.0).round() / 10.0;
+        bg = (bg + expected_delta + extra_at(i)).max(39.0);
+        out.push(bg);
+    }
+
+    out
+}
+
+/// Assemble the four DOC 9/10 prediction arrays
+pub fn predict_arrays(
+    glucose_status: &GlucoseStatus,
+    iob_data: &IOBData,
+    meal_data: &MealData,
+    profile: &Profile,
+) -> GlucosePredictions {
+    let bg = glucose_status.glucose;
+    let sens = profile.sens;
+    let target_bg = profile.min_bg;
+    let dia_blocks = dia_in_5min_blocks(profile.dia);
+    let eventual_bg = calculate_eventual_bg(glucose_status, iob_data, profile);
+
+    // Insulin activity decays toward zero over the DIA; this mirrors the
+    // exponential falloff already used for the simpler single-curve
+    // predictions, just sampled per step instead of from elapsed minutes.
+    let activity_at = |i: usize| {
+        let minutes = i as f64 * 5.0;
+        iob_data.activity * (-minutes / 60.0).exp()
+    };
+
+    let iob_pred_bg = project(bg, target_bg, eventual_bg, dia_blocks, sens, activity_at, |_| 0.0);
+
+    // Zero-temp: same insulin activity decay, but BG also drifts up at the
+    // scheduled basal's usual rate since none is being delivered.
+    let basal_rise_per_step = (profile.current_basal / 60.0) * 5.0 * sens * 0.5;
+    let zt_pred_bg = project(bg, target_bg, eventual_bg, dia_blocks, sens, activity_at, |_| {
+        basal_rise_per_step
+    });
+
+    // COB: IOB effect plus carb absorption, peaking around 45 minutes in and
+    // tapering off once modeled absorption would be exhausted.
+    let carb_ratio = profile.carb_ratio.max(1.0);
+    let cob = meal_data.meal_cob;
+    let cob_extra_at = |i: usize| {
+        if cob <= 0.0 {
+            return 0.0;
+        }
+        let minutes = i as f64 * 5.0;
+        let absorption_peak = (-((minutes - 45.0) / 30.0).powi(2)).exp();
+        (cob / carb_ratio) * sens * 0.1 * absorption_peak
+    };
+    let cob_pred_bg = project(bg, target_bg, eventual_bg, dia_blocks, sens, activity_at, cob_extra_at);
+
+    // UAM: an unannounced meal is modeled as the current deviation persisting
+    // and decaying slowly, rather than being explained by entered carbs.
+    let uam_extra_at = |i: usize| {
+        let minutes = i as f64 * 5.0;
+        let decay = (-minutes / 60.0).exp();
+        glucose_status.delta.max(0.0) * decay
+    };
+    let uam_pred_bg = project(bg, target_bg, eventual_bg, dia_blocks, sens, activity_at, uam_extra_at);
+
+    let min_pred_bg = iob_pred_bg
+        .iter()
+        .chain(cob_pred_bg.iter())
+        .chain(uam_pred_bg.iter())
+        .cloned()
+        .fold(f64::INFINITY, f64::min);
+
+    let max_pred_bg = iob_pred_bg
+        .iter()
+        .chain(cob_pred_bg.iter())
+        .chain(uam_pred_bg.iter())
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    let min_zt_guard_bg = zt_pred_bg.iter().cloned().fold(f64::INFINITY, f64::min);
+
+    GlucosePredictions {
+        iob_pred_bg,
+        zt_pred_bg,
+        cob_pred_bg,
+        uam_pred_bg,
+        eventual_bg,
+        min_pred_bg,
+        max_pred_bg,
+        min_zt_guard_bg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_profile() -> Profile {
+        Profile {
+            sens: 50.0,
+            min_bg: 100.0,
+            max_bg: 120.0,
+            dia: 4.0,
+            current_basal: 1.0,
+            carb_ratio: 10.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_arrays_have_expected_length() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 2.0, activity: 0.02, ..Default::default() };
+        let meal_data = MealData::empty();
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        assert_eq!(predictions.iob_pred_bg.len(), STEPS);
+        assert_eq!(predictions.zt_pred_bg.len(), STEPS);
+        assert_eq!(predictions.cob_pred_bg.len(), STEPS);
+        assert_eq!(predictions.uam_pred_bg.len(), STEPS);
+    }
+
+    #[test]
+    fn test_cob_pred_rises_above_iob_pred_with_carbs() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 1.0, activity: 0.01, ..Default::default() };
+        let meal_data = MealData::with_cob(30.0, 30.0);
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        // With carbs on board, the COB array should be higher than the plain
+        // IOB array at the carb-absorption peak (~45 minutes, index 9).
+        assert!(predictions.cob_pred_bg[9] > predictions.iob_pred_bg[9]);
+    }
+
+    #[test]
+    fn test_zt_guard_reflects_basal_suspension() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 0.0, activity: 0.0, ..Default::default() };
+        let meal_data = MealData::empty();
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        // No IOB/activity: each step adds the withheld-basal rise (25/12
+        // mg/dL) to the expected-delta pull toward target ((100-150)/24,
+        // rounded to -2.1), so the ZT array drifts down by 1/60 per step and
+        // bottoms out at its last point instead of falling at the full pull.
+        let expected_min = 150.0 - 47.0 * (2.1 - 25.0 / 12.0);
+        assert!((predictions.min_zt_guard_bg - expected_min).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_no_cob_leaves_cob_pred_equal_to_iob_pred() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 2.0, activity: 0.015, ..Default::default() };
+        let meal_data = MealData::empty();
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        for (cob_val, iob_val) in predictions.cob_pred_bg.iter().zip(predictions.iob_pred_bg.iter()) {
+            assert!((cob_val - iob_val).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_max_pred_bg_is_overall_maximum() {
+        let glucose_status = GlucoseStatus::new(150.0, 2.0);
+        let iob_data = IOBData { iob: 1.0, activity: 0.01, ..Default::default() };
+        let meal_data = MealData::with_cob(40.0, 40.0);
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        let true_max = predictions
+            .iob_pred_bg
+            .iter()
+            .chain(predictions.cob_pred_bg.iter())
+            .chain(predictions.uam_pred_bg.iter())
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        assert!((predictions.max_pred_bg - true_max).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_min_pred_bg_is_overall_minimum() {
+        let glucose_status = GlucoseStatus::new(150.0, -2.0);
+        let iob_data = IOBData { iob: 3.0, activity: 0.03, ..Default::default() };
+        let meal_data = MealData::empty();
+        let profile = make_profile();
+
+        let predictions = predict_arrays(&glucose_status, &iob_data, &meal_data, &profile);
+
+        let true_min = predictions
+            .iob_pred_bg
+            .iter()
+            .chain(predictions.cob_pred_bg.iter())
+            .chain(predictions.uam_pred_bg.iter())
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+
+        assert!((predictions.min_pred_bg - true_min).abs() < 0.001);
+    }
+}
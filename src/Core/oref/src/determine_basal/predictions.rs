@@ -1,13 +1,41 @@
 //! Glucose prediction calculations
 
+use crate::profile::dynamic_isf::{dynamic_isf, TDDInputs};
 use crate::types::{GlucoseStatus, IOBData, MealData, Profile};
 
+/// Safety bounds applied to dynamic ISF relative to the profile's static value
+const DYNAMIC_ISF_FLOOR_MULT: f64 = 0.7;
+const DYNAMIC_ISF_CEIL_MULT: f64 = 1.3;
+
+/// Whether predictions use the profile's fixed ISF or recompute it from TDD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InsulinSensitivityMode {
+    /// Use `profile.sens` unchanged at every predicted BG level
+    #[default]
+    Static,
+    /// Recompute sensitivity at each predicted BG level via the log-based
+    /// "1800 rule" (see `profile::dynamic_isf`)
+    Dynamic,
+}
+
 /// Predict future glucose values
+///
+/// With `profile.sens_mode == Dynamic` and `tdd` provided, sensitivity is
+/// recomputed at every step from the *previously predicted* BG instead of
+/// held fixed, so it tightens as BG rises and loosens as it falls. Falls
+/// back to the static single-`sens` model otherwise.
 pub fn predict_glucose(
     glucose_status: &GlucoseStatus,
     iob_data: &IOBData,
     profile: &Profile,
+    tdd: Option<&TDDInputs>,
 ) -> Vec<f64> {
+    if profile.sens_mode == InsulinSensitivityMode::Dynamic {
+        if let Some(tdd) = tdd {
+            return predict_glucose_dynamic(glucose_status, iob_data, profile, tdd);
+        }
+    }
+
     let mut predictions = Vec::new();
     let bg = glucose_status.glucose;
     let sens = profile.sens;
@@ -32,14 +60,112 @@ pub fn predict_glucose(
     predictions
 }
 
-/// Calculate eventual BG (where BG is heading)
+/// Dynamic-ISF prediction: sensitivity is recomputed from the running
+/// predicted BG at each 5-minute step: `next = prev + round(-activity *
+/// isf(prev) * 5, 2)`.
+///
+/// Uses `profile.effective_peak_time()` (not raw `profile.peak`) so the
+/// insulin-divisor lookup agrees with `effective_isf_lookup`'s, which is
+/// what actually drives `insulin_req` - otherwise the dose and the
+/// displayed trajectory could be computed against different peaks.
+fn predict_glucose_dynamic(
+    glucose_status: &GlucoseStatus,
+    iob_data: &IOBData,
+    profile: &Profile,
+    tdd: &TDDInputs,
+) -> Vec<f64> {
+    let mut predictions = Vec::with_capacity(48);
+    let mut bg = glucose_status.glucose;
+
+    for i in 0..48 {
+        let minutes = i as f64 * 5.0;
+        let iob_factor = (-minutes / 60.0).exp();
+        let activity = iob_data.activity * iob_factor;
+
+        let isf = dynamic_isf(
+            bg,
+            tdd,
+            profile.effective_peak_time(),
+            profile.sens,
+            DYNAMIC_ISF_FLOOR_MULT,
+            DYNAMIC_ISF_CEIL_MULT,
+            profile.dynamic_isf_adjustment,
+        );
+        let step = (-activity * isf * 5.0 * 100.0).round() / 100.0;
+
+        bg = (bg + step).max(39.0);
+        predictions.push(bg);
+    }
+
+    predictions
+}
+
+/// Predict future glucose assuming a zero temp basal is set right now and
+/// held through the remaining DIA ("predBGzt")
+///
+/// Seeds the IOB decay from [`IOBData::iob_with_zero_temp`] (see
+/// [`crate::iob::total::calculate_total_iob`]) when present, which already
+/// accounts for the scheduled basal insulin a zero temp would claw back;
+/// falls back to the current IOB otherwise. Also adds the BG rise expected
+/// from withholding the scheduled basal, so this curve sits above the plain
+/// IOB-decay prediction. `determine_basal` takes the lowest point of this
+/// curve and its IOB-only curve over the first 45 minutes (minGuardBG) and,
+/// when that falls below its guard threshold, holds basal at zero even
+/// though `eventual_bg` is above target.
+///
+/// `sens` is the caller's effective ISF (static or autosens/dynamic-adjusted)
+/// rather than always `profile.sens`, so this safety-guard curve drops by
+/// the same amount per unit of IOB as the dose calculation actually assumes.
+pub fn predict_glucose_zero_temp(
+    glucose_status: &GlucoseStatus,
+    iob_data: &IOBData,
+    profile: &Profile,
+    sens: f64,
+) -> Vec<f64> {
+    let bg = glucose_status.glucose;
+    let basal = profile.current_basal;
+
+    let zero_temp_iob = match &iob_data.iob_with_zero_temp {
+        Some(zt) => iob_data.iob + zt.iob,
+        None => iob_data.iob,
+    };
+
+    (0..48)
+        .map(|i| {
+            let minutes = i as f64 * 5.0;
+
+            let iob_factor = (-minutes / 60.0).exp();
+            let predicted_iob_effect = zero_temp_iob * iob_factor * sens;
+
+            // BG rises if we stop insulin: trend extrapolation plus the
+            // baseline effect of the withheld scheduled basal
+            let delta_factor = (-minutes / 45.0).exp();
+            let delta_effect = glucose_status.delta.max(0.0) * (minutes / 5.0) * delta_factor;
+            let basal_rise = (basal / 60.0) * minutes * sens * 0.5;
+
+            (bg + delta_effect + basal_rise - predicted_iob_effect).max(39.0)
+        })
+        .collect()
+}
+
+/// Calculate eventual BG (where BG is heading) using the profile's static ISF
 pub fn calculate_eventual_bg(
     glucose_status: &GlucoseStatus,
     iob_data: &IOBData,
     profile: &Profile,
+) -> f64 {
+    calculate_eventual_bg_with_sens(glucose_status, iob_data, profile.sens)
+}
+
+/// Calculate eventual BG using an explicit sensitivity instead of the
+/// profile's static value, so callers that have already resolved an
+/// effective (possibly dynamic) ISF can feed it straight in
+pub fn calculate_eventual_bg_with_sens(
+    glucose_status: &GlucoseStatus,
+    iob_data: &IOBData,
+    sens: f64,
 ) -> f64 {
     let bg = glucose_status.glucose;
-    let sens = profile.sens;
 
     // Eventual BG = current BG - (IOB * sens)
     // This assumes all current IOB will eventually affect BG
@@ -77,6 +203,28 @@ pub fn calculate_bgi(
     (-activity * sens * 5.0 * 100.0).round() / 100.0
 }
 
+/// Calculate BGI using a dynamically-derived ISF instead of a fixed `sens`
+///
+/// Recomputes sensitivity from TDD and the current BG via the log-based
+/// "1800 rule" before applying the same BGI formula as [`calculate_bgi`].
+pub fn calculate_bgi_dynamic(
+    activity: f64,
+    bg: f64,
+    profile: &Profile,
+    tdd: &TDDInputs,
+) -> f64 {
+    let isf = dynamic_isf(
+        bg,
+        tdd,
+        profile.effective_peak_time(),
+        profile.sens,
+        DYNAMIC_ISF_FLOOR_MULT,
+        DYNAMIC_ISF_CEIL_MULT,
+        profile.dynamic_isf_adjustment,
+    );
+    calculate_bgi(activity, isf)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,7 +281,7 @@ mod tests {
             ..Default::default()
         };
 
-        let predictions = predict_glucose(&glucose_status, &iob_data, &profile);
+        let predictions = predict_glucose(&glucose_status, &iob_data, &profile, None);
 
         // With 2U IOB and sens of 50, first prediction accounts for IOB effect
         // predictions[0] = 150 - (2 * 50) = 50 mg/dL
@@ -152,4 +300,91 @@ mod tests {
         // Should be negative (need to come down)
         assert!(expected < 0.0);
     }
+
+    #[test]
+    fn test_dynamic_mode_without_tdd_falls_back_to_static() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 2.0, activity: 0.01, ..Default::default() };
+        let profile = Profile {
+            sens: 50.0,
+            sens_mode: InsulinSensitivityMode::Dynamic,
+            ..Default::default()
+        };
+
+        let dynamic_no_tdd = predict_glucose(&glucose_status, &iob_data, &profile, None);
+        let static_predictions = predict_glucose(
+            &glucose_status,
+            &iob_data,
+            &Profile { sens: 50.0, ..Default::default() },
+            None,
+        );
+
+        assert_eq!(dynamic_no_tdd.len(), static_predictions.len());
+        assert!((dynamic_no_tdd[0] - static_predictions[0]).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_dynamic_mode_uses_per_step_isf() {
+        let glucose_status = GlucoseStatus::new(200.0, 0.0);
+        let iob_data = IOBData { iob: 3.0, activity: 0.02, ..Default::default() };
+        let profile = Profile {
+            sens: 50.0,
+            peak: 55,
+            sens_mode: InsulinSensitivityMode::Dynamic,
+            ..Default::default()
+        };
+        let tdd = TDDInputs::new(40.0, 40.0);
+
+        let predictions = predict_glucose(&glucose_status, &iob_data, &profile, Some(&tdd));
+
+        assert_eq!(predictions.len(), 48);
+        // Insulin activity is pulling BG down from 200
+        assert!(predictions[0] <= 200.0);
+    }
+
+    #[test]
+    fn test_zero_temp_prediction_rises_above_plain_prediction() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let iob_data = IOBData { iob: 2.0, activity: 0.01, ..Default::default() };
+        let profile = Profile { sens: 50.0, current_basal: 1.0, ..Default::default() };
+
+        let plain = predict_glucose(&glucose_status, &iob_data, &profile, None);
+        let zero_temp = predict_glucose_zero_temp(&glucose_status, &iob_data, &profile, profile.sens);
+
+        // Withholding the scheduled basal should predict a higher BG later on
+        assert!(zero_temp[20] > plain[20]);
+    }
+
+    #[test]
+    fn test_zero_temp_prediction_uses_projected_iob_when_present() {
+        let glucose_status = GlucoseStatus::new(150.0, 0.0);
+        let profile = Profile { sens: 50.0, current_basal: 1.0, ..Default::default() };
+
+        let without_projection = IOBData { iob: 2.0, activity: 0.01, ..Default::default() };
+        let with_projection = IOBData {
+            iob: 2.0,
+            activity: 0.01,
+            iob_with_zero_temp: Some(Box::new(IOBData { iob: -0.5, ..Default::default() })),
+            ..Default::default()
+        };
+
+        let a = predict_glucose_zero_temp(&glucose_status, &without_projection, &profile, profile.sens);
+        let b = predict_glucose_zero_temp(&glucose_status, &with_projection, &profile, profile.sens);
+
+        // The projection shows less insulin left to lower BG than the raw
+        // IOB alone, so it should predict a higher BG than ignoring it
+        assert!(b[10] > a[10]);
+    }
+
+    #[test]
+    fn test_bgi_dynamic_matches_static_at_equal_isf() {
+        let profile = Profile { sens: 50.0, peak: 55, ..Default::default() };
+        // A TDD/BG combination chosen so the dynamic ISF clamps to the static value
+        let tdd = TDDInputs::new(0.0, 0.0);
+
+        let dynamic = calculate_bgi_dynamic(0.01, 150.0, &profile, &tdd);
+        let static_bgi = calculate_bgi(0.01, 50.0);
+
+        assert!((dynamic - static_bgi).abs() < 0.01);
+    }
 }
@@ -16,29 +16,81 @@
 //! # Error Handling
 //!
 //! On success, functions return a JSON string with the result.
-//! On error, functions return a JSON string with an "error" field containing
-//! the error message.
+//! On error, functions return a JSON string with a structured `error`
+//! object: `{"error":{"code":"JsonParse","field":"profile","message":"..."}}`.
+//! `code` is a stable [`OrefErrorClass`] name Swift callers can branch on;
+//! `field` (when present) names the argument the failure traces back to;
+//! `message` is a free-text description for logs/humans only.
+//!
+//! # Profiling
+//!
+//! Call `oref_enable_profiling(1)` to start recording call counts and
+//! wall-clock timing per FFI entry point, and `oref_get_profile_stats()` to
+//! read them back as JSON. Profiling is off by default and costs a single
+//! relaxed atomic load per call when disabled.
 
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use chrono::DateTime;
 
 use crate::types::{
-    AutosensData, CurrentTemp, GlucoseReading, GlucoseStatus,
+    AutosensData, COBResult, CurrentTemp, DetermineBasalResult, GlucoseReading, GlucoseStatus,
     IOBData, MealData, Profile, Treatment,
 };
 use crate::determine_basal::DetermineBasalInputs;
 
+// ============================================================================
+// Error Classification
+// ============================================================================
+
+/// Stable, machine-readable classification for an FFI failure
+///
+/// Mirrors the error-class-name approach used by Deno's `errors.rs`: each
+/// underlying failure is mapped to one of a small, fixed set of names so
+/// Swift callers can branch on `code` instead of string-matching `message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrefErrorClass {
+    /// A required pointer argument was null
+    NullPointer,
+    /// A C string argument wasn't valid UTF-8
+    InvalidUtf8,
+    /// `serde_json::from_str` failed to parse an input argument
+    JsonParse,
+    /// A Unix-millis timestamp didn't correspond to a valid `DateTime`
+    InvalidTimestamp,
+    /// The underlying oref algorithm returned an `Err`
+    Domain,
+    /// `serde_json::to_string` failed to serialize a result
+    Serialization,
+}
+
+impl OrefErrorClass {
+    /// The stable string Swift callers match against
+    fn code(self) -> &'static str {
+        match self {
+            OrefErrorClass::NullPointer => "NullPointer",
+            OrefErrorClass::InvalidUtf8 => "InvalidUtf8",
+            OrefErrorClass::JsonParse => "JsonParse",
+            OrefErrorClass::InvalidTimestamp => "InvalidTimestamp",
+            OrefErrorClass::Domain => "Domain",
+            OrefErrorClass::Serialization => "Serialization",
+        }
+    }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-/// Convert a C string pointer to a Rust string slice, returning None if invalid
-unsafe fn c_str_to_rust(ptr: *const c_char) -> Option<&'static str> {
+/// Convert a C string pointer to a Rust string slice, classifying a null
+/// pointer and invalid UTF-8 as distinct failure modes
+unsafe fn c_str_to_rust(ptr: *const c_char) -> Result<&'static str, OrefErrorClass> {
     if ptr.is_null() {
-        return None;
+        return Err(OrefErrorClass::NullPointer);
     }
-    CStr::from_ptr(ptr).to_str().ok()
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| OrefErrorClass::InvalidUtf8)
 }
 
 /// Allocate a new C string from a Rust string, returning null on failure
@@ -48,9 +100,101 @@ fn rust_to_c_string(s: String) -> *mut c_char {
         .unwrap_or(std::ptr::null_mut())
 }
 
-/// Create an error JSON response
-fn error_json(message: &str) -> *mut c_char {
-    rust_to_c_string(format!(r#"{{"error":"{}"}}"#, message.replace('"', "\\\"")))
+/// Build the structured error JSON string
+///
+/// `field` names the argument the failure traces back to (e.g. `"profile"`
+/// for a `Profile` JSON parse failure), and is omitted from the JSON when
+/// not applicable (e.g. a `Domain` error from the algorithm itself).
+fn error_json_string(class: OrefErrorClass, field: Option<&str>, message: &str) -> String {
+    let escaped_message = message.replace('\\', "\\\\").replace('"', "\\\"");
+
+    match field {
+        Some(f) => format!(
+            r#"{{"error":{{"code":"{}","field":"{}","message":"{}"}}}}"#,
+            class.code(),
+            f,
+            escaped_message
+        ),
+        None => format!(
+            r#"{{"error":{{"code":"{}","message":"{}"}}}}"#,
+            class.code(),
+            escaped_message
+        ),
+    }
+}
+
+/// Create a structured error JSON response, allocated as a C string
+fn error_json(class: OrefErrorClass, field: Option<&str>, message: &str) -> *mut c_char {
+    rust_to_c_string(error_json_string(class, field, message))
+}
+
+// ============================================================================
+// Profiling
+// ============================================================================
+
+/// Whether per-call profiling is currently recording. Checked with a relaxed
+/// load so a disabled profiler costs one atomic read per FFI call.
+static PROFILING_ENABLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Call count and wall-clock timing for one FFI entry point
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+struct CallStats {
+    calls: u64,
+    total_ns: u64,
+    last_ns: u64,
+}
+
+/// Global per-function-name profiling registry, created on first use
+fn profile_registry() -> &'static std::sync::Mutex<std::collections::HashMap<&'static str, CallStats>> {
+    static REGISTRY: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<&'static str, CallStats>>> =
+        std::sync::OnceLock::new();
+    REGISTRY.get_or_init(|| std::sync::Mutex::new(std::collections::HashMap::new()))
+}
+
+/// Turn per-call profiling on or off.
+///
+/// When off, `timed` is a single relaxed atomic load plus the wrapped call -
+/// no timer, no lock, no allocation.
+#[no_mangle]
+pub extern "C" fn oref_enable_profiling(on: i32) {
+    PROFILING_ENABLED.store(on != 0, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Get the current profiling counters as JSON.
+///
+/// # Returns
+/// JSON object of `{"<function_name>":{"calls":N,"total_ns":...,"last_ns":...}, ...}`,
+/// one entry per FFI entry point that has been called while profiling was
+/// enabled. Counters accumulate for the life of the process and are not reset
+/// when profiling is turned off and on again. Must be freed with
+/// `oref_free_string`.
+#[no_mangle]
+pub extern "C" fn oref_get_profile_stats() -> *mut c_char {
+    let registry = profile_registry().lock().unwrap();
+    match serde_json::to_string(&*registry) {
+        Ok(json) => rust_to_c_string(json),
+        Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+    }
+}
+
+/// Run `f`, recording its wall-clock duration under `name` in the profiling
+/// registry when profiling is enabled.
+fn timed<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    if !PROFILING_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+        return f();
+    }
+
+    let start = std::time::Instant::now();
+    let result = f();
+    let elapsed_ns = start.elapsed().as_nanos() as u64;
+
+    let mut registry = profile_registry().lock().unwrap();
+    let stats = registry.entry(name).or_default();
+    stats.calls += 1;
+    stats.total_ns += elapsed_ns;
+    stats.last_ns = elapsed_ns;
+
+    result
 }
 
 // ============================================================================
@@ -121,34 +265,38 @@ pub unsafe extern "C" fn oref_calculate_iob(
     time_millis: i64,
     current_only: i32,
 ) -> *mut c_char {
-    let Some(profile_str) = c_str_to_rust(profile_json) else {
-        return error_json("Invalid profile_json pointer");
-    };
-    let Some(treatments_str) = c_str_to_rust(treatments_json) else {
-        return error_json("Invalid treatments_json pointer");
-    };
-
-    let profile: Profile = match serde_json::from_str(profile_str) {
-        Ok(p) => p,
-        Err(e) => return error_json(&format!("Profile parse error: {}", e)),
-    };
-
-    let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
-        Ok(t) => t,
-        Err(e) => return error_json(&format!("Treatments parse error: {}", e)),
-    };
-
-    let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
-        return error_json("Invalid timestamp");
-    };
-
-    match crate::iob::calculate(&profile, &treatments, time, current_only != 0) {
-        Ok(iob_array) => match serde_json::to_string(&iob_array) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        Err(e) => error_json(&e.to_string()),
-    }
+    timed("oref_calculate_iob", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let treatments_str = match c_str_to_rust(treatments_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("treatments_json"), "invalid treatments_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
+            Ok(t) => t,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("treatments"), &e.to_string()),
+        };
+
+        let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
+            return error_json(OrefErrorClass::InvalidTimestamp, Some("time_millis"), "timestamp out of range");
+        };
+
+        match crate::iob::calculate(&profile, &treatments, time, current_only != 0) {
+            Ok(iob_array) => match serde_json::to_string(&iob_array) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json(OrefErrorClass::Domain, None, &e.to_string()),
+        }
+    })
 }
 
 /// Calculate current IOB only (optimized single-point calculation).
@@ -192,42 +340,47 @@ pub unsafe extern "C" fn oref_calculate_cob(
     treatments_json: *const c_char,
     time_millis: i64,
 ) -> *mut c_char {
-    let Some(profile_str) = c_str_to_rust(profile_json) else {
-        return error_json("Invalid profile_json pointer");
-    };
-    let Some(glucose_str) = c_str_to_rust(glucose_json) else {
-        return error_json("Invalid glucose_json pointer");
-    };
-    let Some(treatments_str) = c_str_to_rust(treatments_json) else {
-        return error_json("Invalid treatments_json pointer");
-    };
-
-    let profile: Profile = match serde_json::from_str(profile_str) {
-        Ok(p) => p,
-        Err(e) => return error_json(&format!("Profile parse error: {}", e)),
-    };
-
-    let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
-        Ok(g) => g,
-        Err(e) => return error_json(&format!("Glucose parse error: {}", e)),
-    };
-
-    let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
-        Ok(t) => t,
-        Err(e) => return error_json(&format!("Treatments parse error: {}", e)),
-    };
-
-    let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
-        return error_json("Invalid timestamp");
-    };
-
-    match crate::cob::calculate(&profile, &glucose, &treatments, time) {
-        Ok(cob) => match serde_json::to_string(&cob) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        Err(e) => error_json(&e.to_string()),
-    }
+    timed("oref_calculate_cob", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let glucose_str = match c_str_to_rust(glucose_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_json"), "invalid glucose_json pointer or encoding"),
+        };
+        let treatments_str = match c_str_to_rust(treatments_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("treatments_json"), "invalid treatments_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
+            Ok(g) => g,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose"), &e.to_string()),
+        };
+
+        let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
+            Ok(t) => t,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("treatments"), &e.to_string()),
+        };
+
+        let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
+            return error_json(OrefErrorClass::InvalidTimestamp, Some("time_millis"), "timestamp out of range");
+        };
+
+        match crate::cob::calculate(&profile, &glucose, &treatments, time) {
+            Ok(cob) => match serde_json::to_string(&cob) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json(OrefErrorClass::Domain, None, &e.to_string()),
+        }
+    })
 }
 
 // ============================================================================
@@ -252,42 +405,47 @@ pub unsafe extern "C" fn oref_calculate_autosens(
     treatments_json: *const c_char,
     time_millis: i64,
 ) -> *mut c_char {
-    let Some(profile_str) = c_str_to_rust(profile_json) else {
-        return error_json("Invalid profile_json pointer");
-    };
-    let Some(glucose_str) = c_str_to_rust(glucose_json) else {
-        return error_json("Invalid glucose_json pointer");
-    };
-    let Some(treatments_str) = c_str_to_rust(treatments_json) else {
-        return error_json("Invalid treatments_json pointer");
-    };
-
-    let profile: Profile = match serde_json::from_str(profile_str) {
-        Ok(p) => p,
-        Err(e) => return error_json(&format!("Profile parse error: {}", e)),
-    };
-
-    let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
-        Ok(g) => g,
-        Err(e) => return error_json(&format!("Glucose parse error: {}", e)),
-    };
-
-    let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
-        Ok(t) => t,
-        Err(e) => return error_json(&format!("Treatments parse error: {}", e)),
-    };
-
-    let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
-        return error_json("Invalid timestamp");
-    };
-
-    match crate::autosens::detect_sensitivity(&profile, &glucose, &treatments, time) {
-        Ok(autosens) => match serde_json::to_string(&autosens) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        Err(e) => error_json(&e.to_string()),
-    }
+    timed("oref_calculate_autosens", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let glucose_str = match c_str_to_rust(glucose_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_json"), "invalid glucose_json pointer or encoding"),
+        };
+        let treatments_str = match c_str_to_rust(treatments_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("treatments_json"), "invalid treatments_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
+            Ok(g) => g,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose"), &e.to_string()),
+        };
+
+        let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
+            Ok(t) => t,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("treatments"), &e.to_string()),
+        };
+
+        let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
+            return error_json(OrefErrorClass::InvalidTimestamp, Some("time_millis"), "timestamp out of range");
+        };
+
+        match crate::autosens::detect_sensitivity(&profile, &glucose, &treatments, time) {
+            Ok(autosens) => match serde_json::to_string(&autosens) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json(OrefErrorClass::Domain, None, &e.to_string()),
+        }
+    })
 }
 
 // ============================================================================
@@ -307,6 +465,10 @@ struct DetermineBasalInputsJson {
     #[serde(default)]
     meal_data: MealData,
     #[serde(default)]
+    treatments: Vec<Treatment>,
+    #[serde(default)]
+    pending_insulin: f64,
+    #[serde(default)]
     micro_bolus_allowed: bool,
     #[serde(default)]
     current_time_millis: Option<i64>,
@@ -324,35 +486,40 @@ struct DetermineBasalInputsJson {
 /// JSON string containing DetermineBasalResult. Must be freed with `oref_free_string`.
 #[no_mangle]
 pub unsafe extern "C" fn oref_determine_basal(inputs_json: *const c_char) -> *mut c_char {
-    let Some(inputs_str) = c_str_to_rust(inputs_json) else {
-        return error_json("Invalid inputs_json pointer");
-    };
-
-    let inputs: DetermineBasalInputsJson = match serde_json::from_str(inputs_str) {
-        Ok(i) => i,
-        Err(e) => return error_json(&format!("Inputs parse error: {}", e)),
-    };
-
-    let current_time = inputs.current_time_millis.and_then(DateTime::from_timestamp_millis);
-
-    let algo_inputs = DetermineBasalInputs {
-        glucose_status: &inputs.glucose_status,
-        current_temp: &inputs.current_temp,
-        iob_data: &inputs.iob_data,
-        profile: &inputs.profile,
-        autosens_data: &inputs.autosens_data,
-        meal_data: &inputs.meal_data,
-        micro_bolus_allowed: inputs.micro_bolus_allowed,
-        current_time,
-    };
-
-    match crate::determine_basal::determine_basal(&algo_inputs) {
-        Ok(result) => match serde_json::to_string(&result) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        Err(e) => error_json(&e.to_string()),
-    }
+    timed("oref_determine_basal", || {
+        let inputs_str = match c_str_to_rust(inputs_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("inputs_json"), "invalid inputs_json pointer or encoding"),
+        };
+
+        let inputs: DetermineBasalInputsJson = match serde_json::from_str(inputs_str) {
+            Ok(i) => i,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("inputs"), &e.to_string()),
+        };
+
+        let current_time = inputs.current_time_millis.and_then(DateTime::from_timestamp_millis);
+
+        let algo_inputs = DetermineBasalInputs {
+            glucose_status: &inputs.glucose_status,
+            current_temp: &inputs.current_temp,
+            iob_data: &inputs.iob_data,
+            profile: &inputs.profile,
+            autosens_data: &inputs.autosens_data,
+            meal_data: &inputs.meal_data,
+            treatments: &inputs.treatments,
+            pending_insulin: inputs.pending_insulin,
+            micro_bolus_allowed: inputs.micro_bolus_allowed,
+            current_time,
+        };
+
+        match crate::determine_basal::determine_basal(&algo_inputs) {
+            Ok(result) => match serde_json::to_string(&result) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json(OrefErrorClass::Domain, None, &e.to_string()),
+        }
+    })
 }
 
 /// Convenience function to run determine_basal with individual parameters.
@@ -370,60 +537,448 @@ pub unsafe extern "C" fn oref_determine_basal_simple(
     meal_cob: f64,
     micro_bolus_allowed: i32,
 ) -> *mut c_char {
-    let Some(profile_str) = c_str_to_rust(profile_json) else {
-        return error_json("Invalid profile_json pointer");
-    };
-    let Some(glucose_status_str) = c_str_to_rust(glucose_status_json) else {
-        return error_json("Invalid glucose_status_json pointer");
-    };
-    let Some(iob_data_str) = c_str_to_rust(iob_data_json) else {
-        return error_json("Invalid iob_data_json pointer");
-    };
-    let Some(current_temp_str) = c_str_to_rust(current_temp_json) else {
-        return error_json("Invalid current_temp_json pointer");
-    };
-
-    let profile: Profile = match serde_json::from_str(profile_str) {
-        Ok(p) => p,
-        Err(e) => return error_json(&format!("Profile parse error: {}", e)),
-    };
-
-    let glucose_status: GlucoseStatus = match serde_json::from_str(glucose_status_str) {
-        Ok(g) => g,
-        Err(e) => return error_json(&format!("GlucoseStatus parse error: {}", e)),
-    };
-
-    let iob_data: IOBData = match serde_json::from_str(iob_data_str) {
-        Ok(i) => i,
-        Err(e) => return error_json(&format!("IOBData parse error: {}", e)),
-    };
-
-    let current_temp: CurrentTemp = match serde_json::from_str(current_temp_str) {
-        Ok(c) => c,
-        Err(e) => return error_json(&format!("CurrentTemp parse error: {}", e)),
-    };
-
-    let autosens_data = AutosensData::with_ratio(autosens_ratio);
-    let meal_data = MealData::with_cob(meal_cob, 0.0);
-
-    let inputs = DetermineBasalInputs {
-        glucose_status: &glucose_status,
-        current_temp: &current_temp,
-        iob_data: &iob_data,
-        profile: &profile,
-        autosens_data: &autosens_data,
-        meal_data: &meal_data,
-        micro_bolus_allowed: micro_bolus_allowed != 0,
-        current_time: None,
-    };
-
-    match crate::determine_basal::determine_basal(&inputs) {
-        Ok(result) => match serde_json::to_string(&result) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        Err(e) => error_json(&e.to_string()),
+    timed("oref_determine_basal_simple", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let glucose_status_str = match c_str_to_rust(glucose_status_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_status_json"), "invalid glucose_status_json pointer or encoding"),
+        };
+        let iob_data_str = match c_str_to_rust(iob_data_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("iob_data_json"), "invalid iob_data_json pointer or encoding"),
+        };
+        let current_temp_str = match c_str_to_rust(current_temp_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("current_temp_json"), "invalid current_temp_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let glucose_status: GlucoseStatus = match serde_json::from_str(glucose_status_str) {
+            Ok(g) => g,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose_status"), &e.to_string()),
+        };
+
+        let iob_data: IOBData = match serde_json::from_str(iob_data_str) {
+            Ok(i) => i,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("iob_data"), &e.to_string()),
+        };
+
+        let current_temp: CurrentTemp = match serde_json::from_str(current_temp_str) {
+            Ok(c) => c,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("current_temp"), &e.to_string()),
+        };
+
+        let autosens_data = AutosensData::with_ratio(autosens_ratio);
+        let meal_data = MealData::with_cob(meal_cob, 0.0);
+
+        let inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob_data,
+            profile: &profile,
+            autosens_data: &autosens_data,
+            meal_data: &meal_data,
+            treatments: &[],
+            pending_insulin: 0.0,
+            micro_bolus_allowed: micro_bolus_allowed != 0,
+            current_time: None,
+        };
+
+        match crate::determine_basal::determine_basal(&inputs) {
+            Ok(result) => match serde_json::to_string(&result) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json(OrefErrorClass::Domain, None, &e.to_string()),
+        }
+    })
+}
+
+// ============================================================================
+// Caller-Provided Output Buffer Variants
+// ============================================================================
+
+/// Write a string's UTF-8 bytes plus a trailing NUL into a caller-owned buffer
+///
+/// Returns the number of bytes written (excluding the NUL) on success, or the
+/// negative of the required buffer size (including the NUL) when `out_buf` is
+/// null or `out_cap` is too small, so the caller can reallocate and retry
+/// without Rust ever allocating or handing over ownership of a string.
+unsafe fn write_into_buf(json: &str, out_buf: *mut c_char, out_cap: usize) -> isize {
+    let bytes = json.as_bytes();
+    let required = bytes.len() + 1; // + NUL terminator
+
+    if out_buf.is_null() || out_cap < required {
+        return -(required as isize);
     }
+
+    let out_slice = std::slice::from_raw_parts_mut(out_buf as *mut u8, out_cap);
+    out_slice[..bytes.len()].copy_from_slice(bytes);
+    out_slice[bytes.len()] = 0;
+
+    bytes.len() as isize
+}
+
+/// `oref_determine_basal`, but serializing into a caller-owned buffer instead
+/// of allocating a new C string.
+///
+/// Avoids the per-call heap allocation and the `oref_free_string` ownership
+/// handoff, so a caller running the loop every five minutes can reuse a
+/// single buffer across iterations.
+///
+/// # Safety
+///
+/// `inputs_json` must be a valid null-terminated UTF-8 string. `out_buf` must
+/// point to a buffer of at least `out_cap` writable bytes, or be null (in
+/// which case only the required size is reported).
+///
+/// # Returns
+/// The number of bytes written (excluding the NUL terminator) on success, or
+/// the negative of the required buffer size (including the NUL) if `out_cap`
+/// is too small - the caller should reallocate to at least that many bytes
+/// and call again.
+#[no_mangle]
+pub unsafe extern "C" fn oref_determine_basal_into(
+    inputs_json: *const c_char,
+    out_buf: *mut c_char,
+    out_cap: usize,
+) -> isize {
+    timed("oref_determine_basal_into", || {
+        let inputs_str = match c_str_to_rust(inputs_json) {
+            Ok(s) => s,
+            Err(class) => {
+                let json = error_json_string(class, Some("inputs_json"), "invalid inputs_json pointer or encoding");
+                return write_into_buf(&json, out_buf, out_cap);
+            }
+        };
+
+        let inputs: DetermineBasalInputsJson = match serde_json::from_str(inputs_str) {
+            Ok(i) => i,
+            Err(e) => {
+                let json = error_json_string(OrefErrorClass::JsonParse, Some("inputs"), &e.to_string());
+                return write_into_buf(&json, out_buf, out_cap);
+            }
+        };
+
+        let current_time = inputs.current_time_millis.and_then(DateTime::from_timestamp_millis);
+
+        let algo_inputs = DetermineBasalInputs {
+            glucose_status: &inputs.glucose_status,
+            current_temp: &inputs.current_temp,
+            iob_data: &inputs.iob_data,
+            profile: &inputs.profile,
+            autosens_data: &inputs.autosens_data,
+            meal_data: &inputs.meal_data,
+            treatments: &inputs.treatments,
+            pending_insulin: inputs.pending_insulin,
+            micro_bolus_allowed: inputs.micro_bolus_allowed,
+            current_time,
+        };
+
+        let json = match crate::determine_basal::determine_basal(&algo_inputs) {
+            Ok(result) => match serde_json::to_string(&result) {
+                Ok(json) => json,
+                Err(e) => error_json_string(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            Err(e) => error_json_string(OrefErrorClass::Domain, None, &e.to_string()),
+        };
+
+        write_into_buf(&json, out_buf, out_cap)
+    })
+}
+
+// ============================================================================
+// Full Loop Cycle
+// ============================================================================
+
+/// Combined result of one `oref_run_cycle` call
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunCycleResult {
+    iob: IOBData,
+    cob: COBResult,
+    autosens: AutosensData,
+    glucose_status: GlucoseStatus,
+    determine_basal: DetermineBasalResult,
+}
+
+/// Run a full loop cycle (IOB -> COB -> autosens -> glucose status -> determine_basal)
+/// in a single FFI call.
+///
+/// Parses `profile_json`/`glucose_json`/`treatments_json`/`current_temp_json` once and
+/// chains the existing `crate::iob`, `crate::cob`, `crate::autosens`,
+/// `GlucoseStatus::from_readings`, and `crate::determine_basal` calls, so a caller that
+/// wants a full dosing decision doesn't need five separate FFI round-trips re-parsing
+/// the same JSON each time.
+///
+/// # Safety
+///
+/// All string pointers must be valid null-terminated UTF-8 strings.
+///
+/// # Returns
+/// JSON string containing `{iob, cob, autosens, glucoseStatus, determineBasal}`.
+/// Must be freed with `oref_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn oref_run_cycle(
+    profile_json: *const c_char,
+    glucose_json: *const c_char,
+    treatments_json: *const c_char,
+    current_temp_json: *const c_char,
+    time_millis: i64,
+    micro_bolus_allowed: i32,
+) -> *mut c_char {
+    timed("oref_run_cycle", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let glucose_str = match c_str_to_rust(glucose_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_json"), "invalid glucose_json pointer or encoding"),
+        };
+        let treatments_str = match c_str_to_rust(treatments_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("treatments_json"), "invalid treatments_json pointer or encoding"),
+        };
+        let current_temp_str = match c_str_to_rust(current_temp_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("current_temp_json"), "invalid current_temp_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
+            Ok(g) => g,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose"), &e.to_string()),
+        };
+
+        let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
+            Ok(t) => t,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("treatments"), &e.to_string()),
+        };
+
+        let current_temp: CurrentTemp = match serde_json::from_str(current_temp_str) {
+            Ok(c) => c,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("current_temp"), &e.to_string()),
+        };
+
+        let Some(time) = DateTime::from_timestamp_millis(time_millis) else {
+            return error_json(OrefErrorClass::InvalidTimestamp, Some("time_millis"), "timestamp out of range");
+        };
+
+        let iob = match crate::iob::calculate_current(&profile, &treatments, time) {
+            Ok(i) => i,
+            Err(e) => return error_json(OrefErrorClass::Domain, Some("iob"), &e.to_string()),
+        };
+
+        let cob = match crate::cob::calculate(&profile, &glucose, &treatments, time) {
+            Ok(c) => c,
+            Err(e) => return error_json(OrefErrorClass::Domain, Some("cob"), &e.to_string()),
+        };
+
+        let autosens = match crate::autosens::detect_sensitivity(&profile, &glucose, &treatments, time) {
+            Ok(a) => a,
+            Err(e) => return error_json(OrefErrorClass::Domain, Some("autosens"), &e.to_string()),
+        };
+
+        let Some(glucose_status) = GlucoseStatus::from_readings(&glucose) else {
+            return error_json(OrefErrorClass::Domain, Some("glucose_status"), "no valid glucose readings");
+        };
+
+        let meal_data = MealData::with_cob(cob.meal_cob, 0.0);
+
+        let algo_inputs = DetermineBasalInputs {
+            glucose_status: &glucose_status,
+            current_temp: &current_temp,
+            iob_data: &iob,
+            profile: &profile,
+            autosens_data: &autosens,
+            meal_data: &meal_data,
+            treatments: &treatments,
+            pending_insulin: 0.0,
+            micro_bolus_allowed: micro_bolus_allowed != 0,
+            current_time: Some(time),
+        };
+
+        let determine_basal = match crate::determine_basal::determine_basal(&algo_inputs) {
+            Ok(r) => r,
+            Err(e) => return error_json(OrefErrorClass::Domain, Some("determine_basal"), &e.to_string()),
+        };
+
+        let result = RunCycleResult {
+            iob,
+            cob,
+            autosens,
+            glucose_status,
+            determine_basal,
+        };
+
+        match serde_json::to_string(&result) {
+            Ok(json) => rust_to_c_string(json),
+            Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+        }
+    })
+}
+
+// ============================================================================
+// Historical Replay / Backtest
+// ============================================================================
+
+/// One simulated step of an `oref_replay` run
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplayStep {
+    time: i64,
+    iob: IOBData,
+    cob: COBResult,
+    determine_basal: DetermineBasalResult,
+}
+
+/// Replay/backtest `determine_basal` over a historical time range.
+///
+/// Walks `[start_millis, end_millis]` in `step_millis` increments and, at each step,
+/// recomputes IOB/COB/autosens/glucose-status from only the glucose and treatment data
+/// at-or-before that step's timestamp before running `determine_basal`. Steps with no
+/// glucose data yet (e.g. before the first recorded reading) are skipped rather than
+/// erroring, since the algorithm legitimately has nothing to decide on yet. The key
+/// invariant is that later data is never visible to an earlier step, so the replay
+/// reflects what the algorithm would actually have seen running live.
+///
+/// # Safety
+///
+/// All string pointers must be valid null-terminated UTF-8 strings.
+///
+/// # Returns
+/// JSON array of `{time, determineBasal, iob, cob}` entries. Must be freed with
+/// `oref_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn oref_replay(
+    profile_json: *const c_char,
+    glucose_json: *const c_char,
+    treatments_json: *const c_char,
+    start_millis: i64,
+    end_millis: i64,
+    step_millis: i64,
+) -> *mut c_char {
+    timed("oref_replay", || {
+        let profile_str = match c_str_to_rust(profile_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("profile_json"), "invalid profile_json pointer or encoding"),
+        };
+        let glucose_str = match c_str_to_rust(glucose_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_json"), "invalid glucose_json pointer or encoding"),
+        };
+        let treatments_str = match c_str_to_rust(treatments_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("treatments_json"), "invalid treatments_json pointer or encoding"),
+        };
+
+        let profile: Profile = match serde_json::from_str(profile_str) {
+            Ok(p) => p,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("profile"), &e.to_string()),
+        };
+
+        let glucose: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
+            Ok(g) => g,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose"), &e.to_string()),
+        };
+
+        let treatments: Vec<Treatment> = match serde_json::from_str(treatments_str) {
+            Ok(t) => t,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("treatments"), &e.to_string()),
+        };
+
+        if step_millis <= 0 {
+            return error_json(OrefErrorClass::Domain, Some("step_millis"), "step_millis must be positive");
+        }
+
+        let current_temp = CurrentTemp::default();
+        let mut steps = Vec::new();
+        let mut t = start_millis;
+
+        while t <= end_millis {
+            let Some(time) = DateTime::from_timestamp_millis(t) else {
+                return error_json(OrefErrorClass::InvalidTimestamp, Some("start_millis"), "timestamp out of range");
+            };
+
+            // Never leak future data: only readings/treatments at-or-before this step's time.
+            let glucose_so_far: Vec<GlucoseReading> = glucose
+                .iter()
+                .filter(|g| g.date <= t)
+                .cloned()
+                .collect();
+            let treatments_so_far: Vec<Treatment> = treatments
+                .iter()
+                .filter(|tr| tr.effective_date() <= t)
+                .cloned()
+                .collect();
+
+            t += step_millis;
+
+            let Some(glucose_status) = GlucoseStatus::from_readings(&glucose_so_far) else {
+                // No glucose data yet at this point in history - nothing to decide on.
+                continue;
+            };
+
+            let iob = match crate::iob::calculate_current(&profile, &treatments_so_far, time) {
+                Ok(i) => i,
+                Err(e) => return error_json(OrefErrorClass::Domain, Some("iob"), &e.to_string()),
+            };
+
+            let cob = match crate::cob::calculate(&profile, &glucose_so_far, &treatments_so_far, time) {
+                Ok(c) => c,
+                Err(e) => return error_json(OrefErrorClass::Domain, Some("cob"), &e.to_string()),
+            };
+
+            let autosens = match crate::autosens::detect_sensitivity(&profile, &glucose_so_far, &treatments_so_far, time) {
+                Ok(a) => a,
+                Err(e) => return error_json(OrefErrorClass::Domain, Some("autosens"), &e.to_string()),
+            };
+
+            let meal_data = MealData::with_cob(cob.meal_cob, 0.0);
+
+            let algo_inputs = DetermineBasalInputs {
+                glucose_status: &glucose_status,
+                current_temp: &current_temp,
+                iob_data: &iob,
+                profile: &profile,
+                autosens_data: &autosens,
+                meal_data: &meal_data,
+                treatments: &treatments_so_far,
+                pending_insulin: 0.0,
+                micro_bolus_allowed: false,
+                current_time: Some(time),
+            };
+
+            let determine_basal = match crate::determine_basal::determine_basal(&algo_inputs) {
+                Ok(r) => r,
+                Err(e) => return error_json(OrefErrorClass::Domain, Some("determine_basal"), &e.to_string()),
+            };
+
+            steps.push(ReplayStep {
+                time: time.timestamp_millis(),
+                iob,
+                cob,
+                determine_basal,
+            });
+        }
+
+        match serde_json::to_string(&steps) {
+            Ok(json) => rust_to_c_string(json),
+            Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+        }
+    })
 }
 
 // ============================================================================
@@ -440,22 +995,25 @@ pub unsafe extern "C" fn oref_determine_basal_simple(
 /// JSON string containing GlucoseStatus. Must be freed with `oref_free_string`.
 #[no_mangle]
 pub unsafe extern "C" fn oref_calculate_glucose_status(glucose_json: *const c_char) -> *mut c_char {
-    let Some(glucose_str) = c_str_to_rust(glucose_json) else {
-        return error_json("Invalid glucose_json pointer");
-    };
-
-    let readings: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
-        Ok(r) => r,
-        Err(e) => return error_json(&format!("Glucose parse error: {}", e)),
-    };
-
-    match GlucoseStatus::from_readings(&readings) {
-        Some(status) => match serde_json::to_string(&status) {
-            Ok(json) => rust_to_c_string(json),
-            Err(e) => error_json(&format!("Serialization error: {}", e)),
-        },
-        None => error_json("No valid glucose readings"),
-    }
+    timed("oref_calculate_glucose_status", || {
+        let glucose_str = match c_str_to_rust(glucose_json) {
+            Ok(s) => s,
+            Err(class) => return error_json(class, Some("glucose_json"), "invalid glucose_json pointer or encoding"),
+        };
+
+        let readings: Vec<GlucoseReading> = match serde_json::from_str(glucose_str) {
+            Ok(r) => r,
+            Err(e) => return error_json(OrefErrorClass::JsonParse, Some("glucose"), &e.to_string()),
+        };
+
+        match GlucoseStatus::from_readings(&readings) {
+            Some(status) => match serde_json::to_string(&status) {
+                Ok(json) => rust_to_c_string(json),
+                Err(e) => error_json(OrefErrorClass::Serialization, None, &e.to_string()),
+            },
+            None => error_json(OrefErrorClass::Domain, Some("glucose"), "no valid glucose readings"),
+        }
+    })
 }
 
 // ============================================================================
@@ -506,6 +1064,59 @@ mod tests {
             let c_str = CStr::from_ptr(result);
             let json = c_str.to_str().unwrap();
             assert!(json.contains("error"));
+            assert!(json.contains(r#""code":"NullPointer""#));
+            assert!(json.contains(r#""field":"profile_json""#));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_invalid_json_reports_json_parse_class() {
+        let profile_json = CString::new("not valid json").unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+
+        unsafe {
+            let result = oref_calculate_iob(
+                profile_json.as_ptr(),
+                treatments_json.as_ptr(),
+                chrono::Utc::now().timestamp_millis(),
+                1,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(json.contains(r#""code":"JsonParse""#));
+            assert!(json.contains(r#""field":"profile""#));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_invalid_timestamp_reports_invalid_timestamp_class() {
+        let profile_json = CString::new(r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#).unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+
+        unsafe {
+            let result = oref_calculate_iob(
+                profile_json.as_ptr(),
+                treatments_json.as_ptr(),
+                i64::MAX,
+                1,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(json.contains(r#""code":"InvalidTimestamp""#));
 
             oref_free_string(result);
         }
@@ -545,4 +1156,298 @@ mod tests {
             oref_free_string(result);
         }
     }
+
+    #[test]
+    fn test_run_cycle_null_pointer_handling() {
+        unsafe {
+            let result = oref_run_cycle(
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                0,
+                0,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(json.contains(r#""code":"NullPointer""#));
+            assert!(json.contains(r#""field":"profile_json""#));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_run_cycle_reports_no_glucose_as_domain_error() {
+        let profile_json = CString::new(r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#).unwrap();
+        let glucose_json = CString::new("[]").unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+        let current_temp_json = CString::new(r#"{"duration":0.0,"rate":0.0}"#).unwrap();
+
+        unsafe {
+            let result = oref_run_cycle(
+                profile_json.as_ptr(),
+                glucose_json.as_ptr(),
+                treatments_json.as_ptr(),
+                current_temp_json.as_ptr(),
+                chrono::Utc::now().timestamp_millis(),
+                0,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(json.contains(r#""code":"Domain""#));
+            assert!(json.contains(r#""field":"glucose_status""#));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_run_cycle_runs_full_chain_with_glucose() {
+        let profile_json = CString::new(r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#).unwrap();
+        let now_millis = chrono::Utc::now().timestamp_millis();
+        let glucose_json = CString::new(format!(
+            r#"[{{"glucose":110.0,"date":{}}}]"#,
+            now_millis
+        )).unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+        let current_temp_json = CString::new(r#"{"duration":0.0,"rate":0.0}"#).unwrap();
+
+        unsafe {
+            let result = oref_run_cycle(
+                profile_json.as_ptr(),
+                glucose_json.as_ptr(),
+                treatments_json.as_ptr(),
+                current_temp_json.as_ptr(),
+                now_millis,
+                0,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(!json.contains("error"));
+            assert!(json.contains("determineBasal"));
+            assert!(json.contains("glucoseStatus"));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_replay_rejects_non_positive_step() {
+        let profile_json = CString::new(r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#).unwrap();
+        let glucose_json = CString::new("[]").unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+
+        unsafe {
+            let result = oref_replay(
+                profile_json.as_ptr(),
+                glucose_json.as_ptr(),
+                treatments_json.as_ptr(),
+                0,
+                1000,
+                0,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(json.contains(r#""code":"Domain""#));
+            assert!(json.contains(r#""field":"step_millis""#));
+
+            oref_free_string(result);
+        }
+    }
+
+    #[test]
+    fn test_replay_skips_steps_before_first_reading_and_never_leaks_future_data() {
+        let profile_json = CString::new(r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#).unwrap();
+
+        let start = 0i64;
+        let first_reading_at = 10 * 60_000i64; // 10 minutes in
+        let end = 20 * 60_000i64;
+
+        // Only one reading, placed after `start` - steps before it must be skipped,
+        // and no step should ever see a reading timestamped after itself.
+        let glucose_json = CString::new(format!(
+            r#"[{{"glucose":110.0,"date":{}}}]"#,
+            first_reading_at
+        )).unwrap();
+        let treatments_json = CString::new("[]").unwrap();
+
+        unsafe {
+            let result = oref_replay(
+                profile_json.as_ptr(),
+                glucose_json.as_ptr(),
+                treatments_json.as_ptr(),
+                start,
+                end,
+                5 * 60_000,
+            );
+            let json = CStr::from_ptr(result).to_str().unwrap();
+
+            assert!(!json.contains("error"));
+
+            let steps: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+            // 0 and 5min steps have no glucose yet and are skipped; 10min, 15min, 20min remain.
+            assert_eq!(steps.len(), 3);
+            assert_eq!(steps[0]["time"].as_i64().unwrap(), first_reading_at);
+
+            oref_free_string(result);
+        }
+    }
+
+    fn determine_basal_inputs_json() -> CString {
+        CString::new(r#"{
+            "glucoseStatus": {"glucose": 150.0, "delta": 0.0},
+            "currentTemp": {"duration": 0.0, "rate": 0.0},
+            "iobData": {"iob": 0.0, "activity": 0.0},
+            "profile": {
+                "dia": 3.0,
+                "currentBasal": 1.0,
+                "maxIob": 10.0,
+                "maxDailyBasal": 2.0,
+                "maxBasal": 4.0,
+                "minBg": 100.0,
+                "maxBg": 120.0,
+                "sens": 50.0,
+                "carbRatio": 10.0
+            }
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_determine_basal_into_reports_required_size_when_buffer_too_small() {
+        let inputs_json = determine_basal_inputs_json();
+
+        unsafe {
+            let mut tiny_buf = [0u8; 4];
+            let written = oref_determine_basal_into(
+                inputs_json.as_ptr(),
+                tiny_buf.as_mut_ptr() as *mut c_char,
+                tiny_buf.len(),
+            );
+
+            assert!(written < 0);
+
+            // Retry with a buffer sized to the reported requirement and it must fit.
+            let required = (-written) as usize;
+            let mut big_buf = vec![0u8; required];
+            let written2 = oref_determine_basal_into(
+                inputs_json.as_ptr(),
+                big_buf.as_mut_ptr() as *mut c_char,
+                big_buf.len(),
+            );
+
+            assert!(written2 >= 0);
+            assert_eq!(written2 as usize, required - 1);
+        }
+    }
+
+    #[test]
+    fn test_determine_basal_into_matches_allocating_version() {
+        let inputs_json = determine_basal_inputs_json();
+
+        unsafe {
+            let allocated = oref_determine_basal(inputs_json.as_ptr());
+            let allocated_str = CStr::from_ptr(allocated).to_str().unwrap().to_string();
+            oref_free_string(allocated);
+
+            let mut buf = vec![0u8; allocated_str.len() + 1];
+            let written = oref_determine_basal_into(
+                inputs_json.as_ptr(),
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+            );
+
+            assert_eq!(written as usize, allocated_str.len());
+            let buf_str = CStr::from_ptr(buf.as_ptr() as *const c_char).to_str().unwrap();
+            assert_eq!(buf_str, allocated_str);
+        }
+    }
+
+    #[test]
+    fn test_determine_basal_into_null_buffer_reports_required_size() {
+        let inputs_json = determine_basal_inputs_json();
+
+        unsafe {
+            let written = oref_determine_basal_into(inputs_json.as_ptr(), std::ptr::null_mut(), 0);
+            assert!(written < 0);
+        }
+    }
+
+    #[test]
+    fn test_profiling_disabled_still_runs_wrapped_call() {
+        oref_enable_profiling(0);
+
+        let mut ran = false;
+        let result = timed("test_profiling_disabled_still_runs_wrapped_call_fn", || {
+            ran = true;
+            42
+        });
+
+        assert!(ran);
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn test_profiling_records_calls_and_timing_when_enabled() {
+        oref_enable_profiling(1);
+
+        let glucose_json = CString::new(r#"[{"glucose":110.0,"date":0}]"#).unwrap();
+        unsafe {
+            let result = oref_calculate_glucose_status(glucose_json.as_ptr());
+            oref_free_string(result);
+            let result = oref_calculate_glucose_status(glucose_json.as_ptr());
+            oref_free_string(result);
+        }
+
+        let stats_json = oref_get_profile_stats();
+        let json = unsafe { CStr::from_ptr(stats_json).to_str().unwrap().to_string() };
+        unsafe { oref_free_string(stats_json) };
+
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let entry = &stats["oref_calculate_glucose_status"];
+        assert!(entry["calls"].as_u64().unwrap() >= 2);
+        assert!(entry["total_ns"].as_u64().unwrap() >= entry["last_ns"].as_u64().unwrap());
+
+        oref_enable_profiling(0);
+    }
 }
@@ -0,0 +1,222 @@
+//! Normalization of vendor-specific raw CGM/pump records into [`GlucoseReading`]
+//!
+//! Real pump/CGM dumps (e.g. Medtronic CareLink `GlucoseSensorData` records)
+//! arrive as a mix of record kinds tagged by a `_type` field, only some of
+//! which carry a glucose value, and often timestamp it with a local-offset
+//! date string rather than an epoch value. This module picks the
+//! glucose-bearing records out of that mix, converts their timestamp to UTC
+//! epoch millis, and drops everything else, so the result is ready to feed
+//! straight into [`crate::types::GlucoseStatus::from_readings`] or
+//! [`crate::cob::calculate`].
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use crate::types::GlucoseReading;
+
+/// Maps a raw record's `_type` value to the name of the field on that record
+/// holding its mg/dL glucose value
+///
+/// Add an entry here to recognize another vendor's record kind without
+/// touching the parsing logic itself.
+const GLUCOSE_TYPE_FIELDS: &[(&str, &str)] = &[
+    ("GlucoseSensorData", "sgv"),
+    ("CGMBGCheck", "sgv"),
+    ("sgv", "sgv"),
+];
+
+/// Local-time formats tried in order when a record's timestamp isn't already
+/// an epoch number
+const LOCAL_DATE_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S"];
+
+/// Result of normalizing a batch of raw records
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct NormalizeGlucoseResult {
+    /// Accepted readings, most recent first
+    pub readings: Vec<GlucoseReading>,
+    /// Number of input records mapped to a reading
+    pub accepted: usize,
+    /// Number of input records dropped (unrecognized type, missing value,
+    /// value below 39 mg/dL, or unparseable timestamp)
+    pub rejected: usize,
+}
+
+/// Normalize a batch of raw vendor records into clean glucose readings
+///
+/// `offset_minutes` is the caller's local UTC offset (e.g. `-300` for
+/// US Eastern Standard Time), applied to any record whose timestamp arrives
+/// as a local-time string rather than an epoch value. Readings are returned
+/// most-recent-first, matching the order the rest of oref expects.
+pub fn normalize_glucose(records: &[Value], offset_minutes: i64) -> NormalizeGlucoseResult {
+    let mut readings = Vec::new();
+    let mut accepted = 0usize;
+    let mut rejected = 0usize;
+
+    for record in records {
+        match normalize_one(record, offset_minutes) {
+            Some(reading) => {
+                readings.push(reading);
+                accepted += 1;
+            }
+            None => rejected += 1,
+        }
+    }
+
+    readings.sort_by_key(|r| std::cmp::Reverse(r.date));
+
+    NormalizeGlucoseResult {
+        readings,
+        accepted,
+        rejected,
+    }
+}
+
+/// Normalize a single raw record, or `None` if it isn't a recognized
+/// glucose-bearing record kind
+fn normalize_one(record: &Value, offset_minutes: i64) -> Option<GlucoseReading> {
+    let record_type = record.get("_type")?.as_str()?;
+    let field = GLUCOSE_TYPE_FIELDS
+        .iter()
+        .find(|(t, _)| *t == record_type)
+        .map(|(_, field)| *field)?;
+
+    let glucose = record.get(field)?.as_f64()?;
+    if glucose < 39.0 {
+        return None;
+    }
+
+    let date = record_date_millis(record, offset_minutes)?;
+
+    Some(GlucoseReading::new(glucose, date))
+}
+
+/// Extract a record's timestamp as UTC epoch millis
+///
+/// Prefers an already-numeric `date`/`dateMillis` field; otherwise parses a
+/// `date`/`dateString` field as a local-time string and shifts it by
+/// `offset_minutes` to get UTC.
+fn record_date_millis(record: &Value, offset_minutes: i64) -> Option<i64> {
+    if let Some(millis) = record
+        .get("date")
+        .and_then(Value::as_i64)
+        .or_else(|| record.get("dateMillis").and_then(Value::as_i64))
+    {
+        return Some(millis);
+    }
+
+    let date_string = record
+        .get("date")
+        .and_then(Value::as_str)
+        .or_else(|| record.get("dateString").and_then(Value::as_str))?;
+
+    parse_local_date(date_string, offset_minutes)
+}
+
+/// Parse a local-time string (no timezone) and convert it to UTC epoch
+/// millis by subtracting `offset_minutes`
+fn parse_local_date(date_string: &str, offset_minutes: i64) -> Option<i64> {
+    let naive = LOCAL_DATE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(date_string, fmt).ok())?;
+
+    Some(naive.and_utc().timestamp_millis() - offset_minutes * 60_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_recognized_type_with_epoch_date_is_accepted() {
+        let records = vec![json!({"_type": "GlucoseSensorData", "sgv": 120.0, "date": 1_700_000_000_000i64})];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.accepted, 1);
+        assert_eq!(result.rejected, 0);
+        assert_eq!(result.readings[0].glucose, 120.0);
+        assert_eq!(result.readings[0].date, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_local_date_string_is_shifted_to_utc() {
+        // Local time 10:00:00 at UTC-300 (Eastern Standard) is 15:00:00 UTC
+        let records = vec![json!({
+            "_type": "GlucoseSensorData",
+            "sgv": 110.0,
+            "date": "2024-01-15T10:00:00",
+        })];
+
+        let result = normalize_glucose(&records, -300);
+
+        let expected = NaiveDateTime::parse_from_str("2024-01-15T15:00:00", "%Y-%m-%dT%H:%M:%S")
+            .unwrap()
+            .and_utc()
+            .timestamp_millis();
+        assert_eq!(result.readings[0].date, expected);
+    }
+
+    #[test]
+    fn test_unrecognized_type_is_rejected() {
+        let records = vec![json!({"_type": "TempBasal", "rate": 1.0})];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.accepted, 0);
+        assert_eq!(result.rejected, 1);
+        assert!(result.readings.is_empty());
+    }
+
+    #[test]
+    fn test_missing_glucose_value_is_rejected() {
+        let records = vec![json!({"_type": "GlucoseSensorData", "date": 1_700_000_000_000i64})];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.accepted, 0);
+        assert_eq!(result.rejected, 1);
+    }
+
+    #[test]
+    fn test_below_floor_glucose_is_rejected() {
+        let records = vec![json!({"_type": "GlucoseSensorData", "sgv": 10.0, "date": 1_700_000_000_000i64})];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.accepted, 0);
+        assert_eq!(result.rejected, 1);
+    }
+
+    #[test]
+    fn test_results_are_sorted_most_recent_first() {
+        let records = vec![
+            json!({"_type": "GlucoseSensorData", "sgv": 100.0, "date": 1_700_000_000_000i64}),
+            json!({"_type": "GlucoseSensorData", "sgv": 105.0, "date": 1_700_000_300_000i64}),
+        ];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.readings[0].date, 1_700_000_300_000);
+        assert_eq!(result.readings[1].date, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_mixed_batch_counts_accepted_and_rejected_separately() {
+        let records = vec![
+            json!({"_type": "GlucoseSensorData", "sgv": 100.0, "date": 1_700_000_000_000i64}),
+            json!({"_type": "TempBasal", "rate": 0.5}),
+            json!({"_type": "sgv", "sgv": 95.0, "date": 1_700_000_300_000i64}),
+        ];
+
+        let result = normalize_glucose(&records, 0);
+
+        assert_eq!(result.accepted, 2);
+        assert_eq!(result.rejected, 1);
+    }
+}
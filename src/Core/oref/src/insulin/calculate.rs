@@ -36,6 +36,43 @@ pub fn calculate_iob_contrib(
     }
 }
 
+/// A fully-specified insulin action model: curve shape plus DIA and peak time
+///
+/// Bundles the parameters `calculate_iob_contrib` needs to dispatch so callers
+/// (SMB/basal logic in particular) can carry "how this profile's insulin behaves"
+/// as a single value and ask it for IOB or activity at any elapsed time.
+#[derive(Debug, Clone, Copy)]
+pub struct InsulinModel {
+    /// Curve shape (bilinear vs exponential)
+    pub curve: InsulinCurve,
+    /// Duration of insulin action (hours)
+    pub dia: f64,
+    /// Peak activity time (minutes); ignored for `Bilinear`
+    pub peak: u32,
+}
+
+impl InsulinModel {
+    /// Build a model from a curve, DIA, and peak time
+    pub fn new(curve: InsulinCurve, dia: f64, peak: u32) -> Self {
+        Self { curve, dia, peak }
+    }
+
+    /// Ultra-rapid analog (e.g. Fiasp, Lyumjev): 55 min peak
+    pub fn ultra_rapid(dia: f64) -> Self {
+        Self::new(InsulinCurve::UltraRapid, dia, 55)
+    }
+
+    /// Standard rapid-acting analog (e.g. Novolog, Humalog): 75 min peak
+    pub fn rapid_acting(dia: f64) -> Self {
+        Self::new(InsulinCurve::RapidActing, dia, 75)
+    }
+
+    /// IOB remaining and current activity at `mins_ago` minutes since the dose
+    pub fn iob_contribution(&self, insulin: f64, mins_ago: f64) -> IOBContrib {
+        calculate_iob_contrib(insulin, mins_ago, self.curve, self.dia, self.peak)
+    }
+}
+
 /// Bilinear insulin action curve
 ///
 /// This is the legacy model using a simple triangular shape:
@@ -145,6 +182,152 @@ impl ExponentialCurve {
     }
 }
 
+/// Calculate IOB/activity contribution for a dose delivered continuously
+/// over an interval, rather than instantaneously
+///
+/// A point dose overstates early activity for temp basals and extended
+/// boluses, which actually trickle in over their duration. This splits
+/// `insulin` evenly across ~1-minute sub-steps between `start_mins_ago` and
+/// `end_mins_ago` (`end_mins_ago` being the more recent edge of the
+/// interval) and sums each sub-step's contribution via
+/// [`calculate_iob_contrib`] — a midpoint-rule approximation of the integral
+/// of the single-dose percent-remaining curve across the delivery window.
+/// A dose that finished delivering more than ~1.05x the DIA window ago
+/// collapses back to the ordinary point-dose formula, since none of its
+/// activity curve remains to resolve.
+pub fn calculate_iob_contrib_continuous(
+    insulin: f64,
+    start_mins_ago: f64,
+    end_mins_ago: f64,
+    curve: InsulinCurve,
+    dia: f64,
+    peak: u32,
+) -> IOBContrib {
+    let duration_minutes = start_mins_ago - end_mins_ago;
+    if duration_minutes <= 0.0 {
+        return calculate_iob_contrib(insulin, end_mins_ago.max(0.0), curve, dia, peak);
+    }
+
+    if end_mins_ago > dia * 60.0 * 1.05 {
+        return IOBContrib::zero();
+    }
+
+    let substeps = duration_minutes.ceil().max(1.0) as usize;
+    let substep_minutes = duration_minutes / substeps as f64;
+    let insulin_per_substep = insulin / substeps as f64;
+
+    let mut total_iob = 0.0;
+    let mut total_activity = 0.0;
+
+    for step in 0..substeps {
+        let substep_mins_ago = start_mins_ago - (step as f64 + 0.5) * substep_minutes;
+        let contrib = calculate_iob_contrib(insulin_per_substep, substep_mins_ago.max(0.0), curve, dia, peak);
+        total_iob += contrib.iob_contrib;
+        total_activity += contrib.activity_contrib;
+    }
+
+    IOBContrib::new(total_iob, total_activity)
+}
+
+/// A custom insulin action model with an explicit peak and DIA
+///
+/// Reuses the exponential curve shape but lets a profile specify its own
+/// peak/DIA pair instead of picking from the built-in rapid/ultra-rapid
+/// presets, for faster analogs (Fiasp, Lyumjev) whose early absorption spike
+/// isn't well captured by the peak=55 exponential model.
+pub struct CustomCurve;
+
+impl CustomCurve {
+    /// Calculate IOB contribution using a caller-specified peak/DIA
+    pub fn calculate(insulin: f64, mins_ago: f64, peak_minutes: u32, dia_hours: f64) -> IOBContrib {
+        ExponentialCurve::calculate(insulin, mins_ago, dia_hours, peak_minutes)
+    }
+}
+
+/// A single (minutes-since-dose, fraction-of-insulin-remaining) sample in a
+/// table-driven insulin curve
+#[derive(Debug, Clone, Copy)]
+pub struct CurveSample {
+    /// Minutes since the dose
+    pub minutes: f64,
+    /// Fraction of the dose still active (1.0 at time of dose, 0.0 once fully absorbed)
+    pub fraction_remaining: f64,
+}
+
+/// A piecewise, table-driven insulin action model
+///
+/// Lets a profile match a published pharmacodynamic curve exactly instead of
+/// approximating it with a bilinear or exponential shape. IOB is linearly
+/// interpolated between samples; activity is derived as the negative slope
+/// between the two samples bracketing `mins_ago`.
+pub struct TableCurve;
+
+impl TableCurve {
+    /// Validate that samples are sorted by ascending time, start at full
+    /// IOB (`fraction_remaining == 1.0`), and decay monotonically to 0
+    pub fn validate(samples: &[CurveSample]) -> Result<(), String> {
+        if samples.is_empty() {
+            return Err("curve table must have at least one sample".to_string());
+        }
+
+        if (samples[0].fraction_remaining - 1.0).abs() > 0.001 {
+            return Err("curve table must start at fraction_remaining = 1.0".to_string());
+        }
+
+        for window in samples.windows(2) {
+            let (a, b) = (window[0], window[1]);
+            if b.minutes <= a.minutes {
+                return Err("curve table samples must be sorted by ascending time".to_string());
+            }
+            if b.fraction_remaining > a.fraction_remaining {
+                return Err("curve table fraction_remaining must decay monotonically".to_string());
+            }
+        }
+
+        if samples[samples.len() - 1].fraction_remaining.abs() > 0.001 {
+            return Err("curve table must end at fraction_remaining = 0.0".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Calculate IOB contribution by linearly interpolating the table
+    ///
+    /// Returns full insulin before the table's first sample, and zero once
+    /// `mins_ago` is past the table's last sample.
+    pub fn calculate(insulin: f64, mins_ago: f64, samples: &[CurveSample]) -> IOBContrib {
+        if samples.is_empty() {
+            return IOBContrib::zero();
+        }
+
+        if mins_ago <= samples[0].minutes {
+            return IOBContrib::new(insulin * samples[0].fraction_remaining, 0.0);
+        }
+
+        let last = samples.last().unwrap();
+        if mins_ago >= last.minutes {
+            return IOBContrib::zero();
+        }
+
+        for window in samples.windows(2) {
+            let (a, b) = (window[0], window[1]);
+            if mins_ago >= a.minutes && mins_ago <= b.minutes {
+                let span = b.minutes - a.minutes;
+                let t = (mins_ago - a.minutes) / span;
+                let fraction = a.fraction_remaining + t * (b.fraction_remaining - a.fraction_remaining);
+
+                // Activity is the negative slope of the fraction-remaining curve
+                let slope_per_min = (b.fraction_remaining - a.fraction_remaining) / span;
+                let activity_contrib = insulin * -slope_per_min;
+
+                return IOBContrib::new(insulin * fraction, activity_contrib);
+            }
+        }
+
+        IOBContrib::zero()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +424,16 @@ mod tests {
         assert_eq!(contrib.activity_contrib, 0.0);
     }
 
+    #[test]
+    fn test_insulin_model_iob_contribution() {
+        let model = InsulinModel::rapid_acting(5.0);
+        let direct = calculate_iob_contrib(1.0, 60.0, InsulinCurve::RapidActing, 5.0, 75);
+        let via_model = model.iob_contribution(1.0, 60.0);
+
+        assert_relative_eq!(via_model.iob_contrib, direct.iob_contrib, epsilon = 0.0001);
+        assert_relative_eq!(via_model.activity_contrib, direct.activity_contrib, epsilon = 0.0001);
+    }
+
     #[test]
     fn test_iob_matches_js_implementation() {
         // Test values from the JS implementation tests
@@ -255,4 +448,119 @@ mod tests {
         // Activity at 1 hour should be positive (insulin is being absorbed)
         assert!(at_60.activity_contrib > 0.0);
     }
+
+    #[test]
+    fn test_continuous_contrib_keeps_more_iob_than_point_dose_early_on() {
+        // A 1 U dose delivered over the last 30 minutes should have more
+        // IOB remaining right now than if the whole unit had been given as
+        // a point dose 30 minutes ago (since most of it was only just
+        // delivered and hasn't started decaying yet)
+        let continuous = calculate_iob_contrib_continuous(1.0, 30.0, 0.0, InsulinCurve::RapidActing, 5.0, 75);
+        let point_dose = calculate_iob_contrib(1.0, 30.0, InsulinCurve::RapidActing, 5.0, 75);
+
+        assert!(continuous.iob_contrib > point_dose.iob_contrib);
+        assert!(continuous.iob_contrib < 1.0);
+    }
+
+    #[test]
+    fn test_continuous_contrib_collapses_to_point_dose_when_duration_is_zero() {
+        let continuous = calculate_iob_contrib_continuous(1.0, 60.0, 60.0, InsulinCurve::RapidActing, 5.0, 75);
+        let point_dose = calculate_iob_contrib(1.0, 60.0, InsulinCurve::RapidActing, 5.0, 75);
+
+        assert_relative_eq!(continuous.iob_contrib, point_dose.iob_contrib, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn test_continuous_contrib_zero_past_dia_window() {
+        let contrib = calculate_iob_contrib_continuous(1.0, 400.0, 350.0, InsulinCurve::RapidActing, 5.0, 75);
+        assert_relative_eq!(contrib.iob_contrib, 0.0, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn test_custom_curve_matches_exponential_with_same_peak_dia() {
+        let custom = CustomCurve::calculate(1.0, 60.0, 55, 5.0);
+        let exponential = ExponentialCurve::calculate(1.0, 60.0, 5.0, 55);
+        assert_relative_eq!(custom.iob_contrib, exponential.iob_contrib, epsilon = 0.0001);
+        assert_relative_eq!(custom.activity_contrib, exponential.activity_contrib, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn test_custom_curve_fiasp_style_early_peak() {
+        // Fiasp-style profile: 35 min peak, 4h DIA
+        let contrib = CustomCurve::calculate(1.0, 35.0, 35, 4.0);
+        assert!(contrib.iob_contrib > 0.0 && contrib.iob_contrib < 1.0);
+        assert!(contrib.activity_contrib > 0.0);
+    }
+
+    fn fiasp_table() -> Vec<CurveSample> {
+        vec![
+            CurveSample { minutes: 0.0, fraction_remaining: 1.0 },
+            CurveSample { minutes: 35.0, fraction_remaining: 0.6 },
+            CurveSample { minutes: 120.0, fraction_remaining: 0.2 },
+            CurveSample { minutes: 240.0, fraction_remaining: 0.0 },
+        ]
+    }
+
+    #[test]
+    fn test_table_curve_validate_accepts_well_formed_table() {
+        assert!(TableCurve::validate(&fiasp_table()).is_ok());
+    }
+
+    #[test]
+    fn test_table_curve_validate_rejects_empty() {
+        assert!(TableCurve::validate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_table_curve_validate_rejects_bad_start() {
+        let samples = vec![
+            CurveSample { minutes: 0.0, fraction_remaining: 0.9 },
+            CurveSample { minutes: 240.0, fraction_remaining: 0.0 },
+        ];
+        assert!(TableCurve::validate(&samples).is_err());
+    }
+
+    #[test]
+    fn test_table_curve_validate_rejects_non_monotonic() {
+        let samples = vec![
+            CurveSample { minutes: 0.0, fraction_remaining: 1.0 },
+            CurveSample { minutes: 60.0, fraction_remaining: 0.5 },
+            CurveSample { minutes: 120.0, fraction_remaining: 0.6 },
+        ];
+        assert!(TableCurve::validate(&samples).is_err());
+    }
+
+    #[test]
+    fn test_table_curve_validate_rejects_unsorted_times() {
+        let samples = vec![
+            CurveSample { minutes: 0.0, fraction_remaining: 1.0 },
+            CurveSample { minutes: 60.0, fraction_remaining: 0.5 },
+            CurveSample { minutes: 30.0, fraction_remaining: 0.7 },
+        ];
+        assert!(TableCurve::validate(&samples).is_err());
+    }
+
+    #[test]
+    fn test_table_curve_interpolates_between_samples() {
+        let table = fiasp_table();
+        // Halfway between 35 (0.6) and 120 (0.2) minutes
+        let contrib = TableCurve::calculate(1.0, 77.5, &table);
+        assert_relative_eq!(contrib.iob_contrib, 0.4, epsilon = 0.001);
+        assert!(contrib.activity_contrib > 0.0);
+    }
+
+    #[test]
+    fn test_table_curve_zero_past_last_sample() {
+        let table = fiasp_table();
+        let contrib = TableCurve::calculate(1.0, 300.0, &table);
+        assert_relative_eq!(contrib.iob_contrib, 0.0, epsilon = 0.001);
+        assert_relative_eq!(contrib.activity_contrib, 0.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn test_table_curve_full_before_first_sample() {
+        let table = fiasp_table();
+        let contrib = TableCurve::calculate(2.0, 0.0, &table);
+        assert_relative_eq!(contrib.iob_contrib, 2.0, epsilon = 0.001);
+    }
 }
@@ -47,55 +47,69 @@ pub fn find_insulin_treatments(
             continue;
         }
 
-        // Handle bolus events
+        // Handle bolus events. An extended/square bolus carries a duration
+        // alongside its insulin amount; keep it so IOB calculation can
+        // treat it as continuously delivered instead of a point dose.
         if let Some(insulin) = event.insulin {
             if insulin > 0.0 {
                 treatments.push(Treatment {
                     insulin: Some(insulin),
+                    duration: event.duration.filter(|d| *d > 0.0 && event.rate.is_none()),
                     date: event_date,
                     timestamp: event.timestamp.clone(),
                     started_at: event.started_at.clone().or_else(|| event.timestamp.clone()),
+                    event_type: Some("Bolus".to_string()),
                     ..Default::default()
                 });
             }
         }
 
         // Handle temp basal events - convert to discrete insulin doses
-        if let (Some(rate), Some(duration)) = (event.rate, event.duration) {
+        if let (Some(_rate), Some(duration)) = (event.rate, event.duration) {
             if duration > 0.0 {
-                // Get scheduled basal rate
-                let scheduled_basal = lookup_basal_at_time(profile, event_date);
-
-                // Calculate net insulin per 5-minute interval
-                let net_rate = rate - scheduled_basal;
-
-                // Split temp basal into 5-minute chunks
-                let chunks = (duration / 5.0).ceil() as i32;
-
-                for chunk in 0..chunks {
-                    let chunk_start = event_date + (chunk as i64 * 5 * 60 * 1000);
-
-                    // Don't add chunks in the future
-                    if chunk_start > now_millis {
-                        break;
-                    }
-
-                    // Calculate insulin for this 5-minute chunk
-                    let chunk_duration = if chunk == chunks - 1 {
-                        // Last chunk might be partial
-                        duration - (chunk as f64 * 5.0)
-                    } else {
-                        5.0
-                    };
-
-                    let chunk_insulin = net_rate * chunk_duration / 60.0;
-
-                    if chunk_insulin.abs() > 0.0001 {
-                        treatments.push(Treatment {
-                            insulin: Some(chunk_insulin),
-                            date: chunk_start,
-                            ..Default::default()
-                        });
+                // A temp basal spanning a schedule change (or midnight) can't
+                // use a single scheduled rate for its whole duration, so
+                // split it at every boundary crossing first.
+                let segments = split_temp_basal_at_schedule_changes(event, profile);
+
+                for segment in &segments {
+                    let seg_rate = segment.rate.unwrap_or(0.0);
+                    let seg_duration = segment.duration.unwrap_or(0.0);
+                    let seg_start = segment.date;
+
+                    // Each segment keeps a constant scheduled rate, looked up once
+                    let scheduled_basal = lookup_basal_at_time(profile, seg_start);
+                    let net_rate = seg_rate - scheduled_basal;
+
+                    // Split the segment into 5-minute chunks
+                    let chunks = (seg_duration / 5.0).ceil() as i32;
+
+                    for chunk in 0..chunks {
+                        let chunk_start = seg_start + (chunk as i64 * 5 * 60 * 1000);
+
+                        // Don't add chunks in the future
+                        if chunk_start > now_millis {
+                            break;
+                        }
+
+                        // Calculate insulin for this 5-minute chunk
+                        let chunk_duration = if chunk == chunks - 1 {
+                            // Last chunk might be partial
+                            seg_duration - (chunk as f64 * 5.0)
+                        } else {
+                            5.0
+                        };
+
+                        let chunk_insulin = net_rate * chunk_duration / 60.0;
+
+                        if chunk_insulin.abs() > 0.0001 {
+                            treatments.push(Treatment {
+                                insulin: Some(chunk_insulin),
+                                date: chunk_start,
+                                event_type: Some("TempBasal".to_string()),
+                                ..Default::default()
+                            });
+                        }
                     }
                 }
             }
@@ -114,6 +128,7 @@ pub fn find_insulin_treatments(
             treatments.push(Treatment {
                 insulin: Some(chunk_insulin),
                 date: chunk_start,
+                event_type: Some("TempBasal".to_string()),
                 ..Default::default()
             });
         }
@@ -164,14 +179,93 @@ fn lookup_basal_at_time(profile: &Profile, time_millis: i64) -> f64 {
 /// Split a temp basal that spans schedule changes
 ///
 /// This handles cases where a temp basal runs across midnight or
-/// when the scheduled basal rate changes during the temp.
+/// when the scheduled basal rate changes during the temp. Each returned
+/// segment keeps the original temp rate but a shorter duration, bounded by
+/// the next basal-schedule boundary (or the 24h wrap), so a caller that
+/// looks up the scheduled rate once per segment gets the right answer for
+/// every part of the temp.
 pub fn split_temp_basal_at_schedule_changes(
     treatment: &Treatment,
     profile: &Profile,
 ) -> Vec<Treatment> {
-    // For now, return the treatment as-is
-    // Full implementation would split at schedule boundaries
-    vec![treatment.clone()]
+    let (rate, duration) = match (treatment.rate, treatment.duration) {
+        (Some(rate), Some(duration)) if duration > 0.0 => (rate, duration),
+        _ => return vec![treatment.clone()],
+    };
+
+    let start = treatment.effective_date();
+    let end = start + (duration * 60.0 * 1000.0).round() as i64;
+
+    let boundaries = schedule_boundaries_between(profile, start, end);
+    if boundaries.is_empty() {
+        return vec![treatment.clone()];
+    }
+
+    let mut segments = Vec::new();
+    let mut segment_start = start;
+
+    for boundary in boundaries {
+        if boundary <= segment_start || boundary >= end {
+            continue;
+        }
+        segments.push(make_segment(treatment, rate, segment_start, boundary));
+        segment_start = boundary;
+    }
+    segments.push(make_segment(treatment, rate, segment_start, end));
+
+    segments
+}
+
+/// Build a single segment Treatment carrying the original temp's rate
+fn make_segment(treatment: &Treatment, rate: f64, start: i64, end: i64) -> Treatment {
+    let duration_minutes = (end - start) as f64 / 60_000.0;
+    Treatment {
+        rate: Some(rate),
+        duration: Some(duration_minutes),
+        date: start,
+        event_type: treatment.event_type.clone(),
+        ..Default::default()
+    }
+}
+
+/// Every basal-schedule boundary (plus the midnight wrap) that falls
+/// strictly between `start` and `end`, as absolute Unix millis
+fn schedule_boundaries_between(profile: &Profile, start: i64, end: i64) -> Vec<i64> {
+    if profile.basal_profile.is_empty() {
+        return Vec::new();
+    }
+
+    let mut minute_boundaries: Vec<u32> = profile.basal_profile.iter().map(|e| e.minutes).collect();
+    minute_boundaries.push(0); // Midnight always counts as a boundary
+    minute_boundaries.sort_unstable();
+    minute_boundaries.dedup();
+
+    let start_dt = DateTime::from_timestamp_millis(start).unwrap_or_else(Utc::now);
+    let ms_into_day = (start_dt.hour() as i64 * 3600 + start_dt.minute() as i64 * 60 + start_dt.second() as i64)
+        * 1000
+        + start_dt.timestamp_subsec_millis() as i64;
+    let day_start = start - ms_into_day;
+
+    let mut boundaries = Vec::new();
+    let mut day = 0i64;
+    loop {
+        let day_base = day_start + day * 24 * 60 * 60 * 1000;
+        if day_base > end {
+            break;
+        }
+
+        for &minutes in &minute_boundaries {
+            let boundary = day_base + minutes as i64 * 60 * 1000;
+            if boundary > start && boundary < end {
+                boundaries.push(boundary);
+            }
+        }
+
+        day += 1;
+    }
+
+    boundaries.sort_unstable();
+    boundaries
 }
 
 use chrono::Timelike;
@@ -254,4 +348,46 @@ mod tests {
             assert!(t.insulin.unwrap_or(0.0) < 0.0);
         }
     }
+
+    #[test]
+    fn test_bolus_and_temp_basal_treatments_are_tagged_by_source() {
+        let now = Utc::now();
+        let profile = make_profile();
+
+        let history = vec![
+            Treatment::bolus(2.0, now - Duration::hours(1)),
+            Treatment::temp_basal(2.0, 30.0, now - Duration::minutes(30)),
+        ];
+
+        let treatments = find_insulin_treatments(&profile, &history, now, 0).unwrap();
+
+        assert_eq!(treatments[0].event_type.as_deref(), Some("Bolus"));
+        assert!(treatments[1..].iter().all(|t| t.event_type.as_deref() == Some("TempBasal")));
+    }
+
+    #[test]
+    fn test_split_without_schedule_returns_original() {
+        let now = Utc::now();
+        let profile = make_profile(); // empty basal_profile
+
+        let temp = Treatment::temp_basal(2.0, 90.0, now - Duration::minutes(90));
+        let segments = split_temp_basal_at_schedule_changes(&temp, &profile);
+
+        // No schedule to split against, so the temp passes through unchanged
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].rate, Some(2.0));
+        assert_eq!(segments[0].duration, Some(90.0));
+    }
+
+    #[test]
+    fn test_split_preserves_total_duration() {
+        let now = Utc::now();
+        let profile = make_profile();
+
+        let temp = Treatment::temp_basal(1.5, 45.0, now - Duration::minutes(45));
+        let segments = split_temp_basal_at_schedule_changes(&temp, &profile);
+
+        let total_duration: f64 = segments.iter().map(|s| s.duration.unwrap_or(0.0)).sum();
+        assert!((total_duration - 45.0).abs() < 0.01);
+    }
 }
@@ -5,9 +5,11 @@
 
 mod history;
 mod total;
+mod pending;
 
 pub use history::find_insulin_treatments;
 pub use total::calculate_total_iob;
+pub use pending::{find_basal_at_time, get_pending_insulin, RunningTemp, ScheduleEntry};
 
 use chrono::{DateTime, Utc};
 use crate::types::{IOBData, Profile, Treatment, TempBasalState};
@@ -0,0 +1,168 @@
+//! Pending (committed but not yet counted) insulin accounting
+//!
+//! IOB only reflects delivered doses. This answers "how much insulin is
+//! already committed but not yet counted" — the net basal a running temp
+//! will still deliver before it ends, plus any unconfirmed bolus — so a
+//! future bolus recommender can subtract it from its suggestion.
+
+/// One entry in a basal rate schedule, keyed by time-of-day
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduleEntry {
+    /// Start of this segment, in minutes since midnight
+    pub start_minutes: u32,
+    /// Scheduled rate for this segment (U/hr)
+    pub rate: f64,
+}
+
+/// A currently-running temp basal, in minutes-of-day
+#[derive(Debug, Clone, Copy)]
+pub struct RunningTemp {
+    /// Temp basal rate (U/hr)
+    pub rate: f64,
+    /// Minutes since midnight from which delivery is counted: the current
+    /// time for a temp that is already running, so only what it will still
+    /// deliver is included
+    pub start_minutes_of_day: u32,
+    /// Minutes since midnight when the temp ends (may wrap past midnight)
+    pub end_minutes_of_day: u32,
+}
+
+/// Find the scheduled basal rate active at a given time-of-day
+///
+/// Entries need not be sorted. Handles wraparound past midnight: if
+/// `minutes_of_day` precedes every entry's start, the last entry of the
+/// schedule (the segment carried over from the previous day) applies.
+pub fn find_basal_at_time(schedule: &[ScheduleEntry], minutes_of_day: u32) -> f64 {
+    if schedule.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted: Vec<&ScheduleEntry> = schedule.iter().collect();
+    sorted.sort_by_key(|e| e.start_minutes);
+
+    let mut rate = sorted.last().unwrap().rate;
+    for entry in &sorted {
+        if minutes_of_day >= entry.start_minutes {
+            rate = entry.rate;
+        } else {
+            break;
+        }
+    }
+
+    rate
+}
+
+/// Net insulin already committed but not yet reflected in IOB
+///
+/// Sums the net basal (running temp rate minus scheduled rate, integrated
+/// in 5-minute steps so a schedule change mid-temp is accounted for) that
+/// will still be delivered before the temp ends, plus any unconfirmed bolus.
+pub fn get_pending_insulin(
+    schedule: &[ScheduleEntry],
+    running_temp: Option<&RunningTemp>,
+    unconfirmed_bolus: Option<f64>,
+) -> f64 {
+    let temp_contribution = running_temp.map_or(0.0, |temp| net_temp_insulin(schedule, temp));
+    temp_contribution + unconfirmed_bolus.unwrap_or(0.0)
+}
+
+/// Net insulin (U) a running temp will deliver beyond scheduled basal
+/// between now and when it ends
+fn net_temp_insulin(schedule: &[ScheduleEntry], temp: &RunningTemp) -> f64 {
+    let total_minutes = minutes_until(temp.start_minutes_of_day, temp.end_minutes_of_day);
+    if total_minutes <= 0.0 {
+        return 0.0;
+    }
+
+    let steps = (total_minutes / 5.0).ceil() as u32;
+    let mut net = 0.0;
+
+    for step in 0..steps {
+        let elapsed = step as f64 * 5.0;
+        let step_minutes = (total_minutes - elapsed).min(5.0);
+        let time_of_day = ((temp.start_minutes_of_day as f64 + elapsed) as u32) % (24 * 60);
+        let scheduled = find_basal_at_time(schedule, time_of_day);
+        net += (temp.rate - scheduled) * step_minutes / 60.0;
+    }
+
+    net
+}
+
+/// Minutes from `start` to `end` (minutes-of-day), wrapping past midnight
+fn minutes_until(start: u32, end: u32) -> f64 {
+    if end >= start {
+        (end - start) as f64
+    } else {
+        (24 * 60 - start + end) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_schedule() -> Vec<ScheduleEntry> {
+        vec![
+            ScheduleEntry { start_minutes: 0, rate: 1.0 },
+            ScheduleEntry { start_minutes: 360, rate: 1.5 }, // 06:00
+            ScheduleEntry { start_minutes: 1320, rate: 0.8 }, // 22:00
+        ]
+    }
+
+    #[test]
+    fn test_find_basal_at_time_mid_segment() {
+        let schedule = make_schedule();
+        // 08:00 -> 480 minutes, falls in the 06:00 segment
+        assert_eq!(find_basal_at_time(&schedule, 480), 1.5);
+    }
+
+    #[test]
+    fn test_find_basal_at_time_wraps_to_last_segment() {
+        let schedule = make_schedule();
+        // 23:00 -> 1380 minutes, falls in the 22:00 segment (carries to midnight)
+        assert_eq!(find_basal_at_time(&schedule, 1380), 0.8);
+    }
+
+    #[test]
+    fn test_find_basal_at_time_empty_schedule() {
+        assert_eq!(find_basal_at_time(&[], 600), 0.0);
+    }
+
+    #[test]
+    fn test_pending_insulin_with_no_temp_or_bolus() {
+        let schedule = make_schedule();
+        assert_eq!(get_pending_insulin(&schedule, None, None), 0.0);
+    }
+
+    #[test]
+    fn test_pending_insulin_includes_unconfirmed_bolus() {
+        let schedule = make_schedule();
+        let pending = get_pending_insulin(&schedule, None, Some(1.2));
+        assert!((pending - 1.2).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_pending_insulin_from_running_temp_above_schedule() {
+        let schedule = make_schedule();
+        // Scheduled rate at 08:00 is 1.5 U/hr; temp runs 2.0 U/hr for 30 min
+        let temp = RunningTemp { rate: 2.0, start_minutes_of_day: 480, end_minutes_of_day: 510 };
+
+        let pending = get_pending_insulin(&schedule, Some(&temp), None);
+
+        // Net rate 0.5 U/hr over 30 min = 0.25 U
+        assert!((pending - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_pending_insulin_handles_midnight_wrap() {
+        let schedule = make_schedule();
+        // Temp starts 23:50 (1430) and runs 20 min past midnight to 00:10 (10)
+        let temp = RunningTemp { rate: 1.0, start_minutes_of_day: 1430, end_minutes_of_day: 10 };
+
+        let pending = get_pending_insulin(&schedule, Some(&temp), None);
+
+        // Scheduled rate throughout is 0.8 (22:00 segment) then 1.0 (midnight segment)
+        // Net should be small but non-panicking and finite
+        assert!(pending.is_finite());
+    }
+}
@@ -1,7 +1,8 @@
 //! Total IOB calculation from all treatments
 
 use chrono::{DateTime, Utc};
-use crate::insulin::calculate_iob_contrib;
+use crate::insulin::{calculate_iob_contrib, calculate_iob_contrib_continuous};
+use crate::profile::basal_lookup;
 use crate::types::{IOBData, Profile, Treatment};
 use crate::Result;
 
@@ -65,14 +66,23 @@ pub fn calculate_total_iob(
         // Calculate minutes since treatment
         let mins_ago = (now_millis - treatment_date) as f64 / 60000.0;
 
-        // Calculate IOB contribution
-        let contrib = calculate_iob_contrib(
-            insulin.abs(),
-            mins_ago,
-            curve,
-            dia,
-            peak,
-        );
+        // An extended/square bolus still carrying a duration is delivered
+        // continuously rather than all at once; treating it as a point
+        // dose would overstate its early activity.
+        let contrib = match treatment.duration {
+            Some(duration) if duration > 0.0 => {
+                let end_mins_ago = (mins_ago - duration).max(0.0);
+                calculate_iob_contrib_continuous(
+                    insulin.abs(),
+                    mins_ago,
+                    end_mins_ago,
+                    curve,
+                    dia,
+                    peak,
+                )
+            }
+            _ => calculate_iob_contrib(insulin.abs(), mins_ago, curve, dia, peak),
+        };
 
         // Apply sign for negative insulin (suspended basal)
         let sign = if insulin < 0.0 { -1.0 } else { 1.0 };
@@ -82,10 +92,17 @@ pub fn calculate_total_iob(
         total_iob += iob_contrib;
         total_activity += activity_contrib;
 
-        // Categorize by source
-        // Small doses (< 0.1 U) are considered basal adjustments
-        // Larger doses are considered boluses
-        if insulin.abs() < 0.1 {
+        // Categorize by source. Treatments tagged by `find_insulin_treatments`
+        // carry an explicit event_type ("Bolus" or "TempBasal"); fall back to
+        // the old dose-size heuristic only for untagged treatments supplied
+        // directly by a caller.
+        let is_basal = match treatment.event_type.as_deref() {
+            Some("TempBasal") => true,
+            Some("Bolus") => false,
+            _ => insulin.abs() < 0.1,
+        };
+
+        if is_basal {
             basal_iob += iob_contrib;
             net_basal_insulin += insulin;
         } else {
@@ -94,7 +111,7 @@ pub fn calculate_total_iob(
         }
     }
 
-    Ok(IOBData {
+    let mut iob_data = IOBData {
         iob: total_iob,
         activity: total_activity,
         basal_iob,
@@ -105,7 +122,99 @@ pub fn calculate_total_iob(
         iob_with_zero_temp: None,
         last_bolus_time: None,
         last_temp: None,
-    })
+    };
+
+    // Routed through the same `project_zero_temp` the rest of the codebase
+    // uses, so there's a single zero-temp projection instead of this field
+    // and `IOBData::project_zero_temp` drifting out of sync
+    if !profile.basal_profile.is_empty() {
+        iob_data.iob_with_zero_temp = Some(iob_data.project_zero_temp(profile, time));
+    }
+
+    Ok(iob_data)
+}
+
+/// Project the IOB still attributable to scheduled basal that a zero temp
+/// set right now could claw back
+///
+/// Synthesizes a virtual negative-insulin "treatment" for each 5-minute
+/// slot across the DIA window leading up to `time`, sized to the scheduled
+/// basal rate for that slot (via [`basal_lookup`]), and sums their IOB and
+/// activity contribution the same way a real delivery would be counted.
+/// This is the `predBGs`/zero-temp concept from oref0 PR #714: it tells a
+/// caller how much of the current IOB is basal they could still suspend
+/// away, as opposed to boluses that are already committed.
+fn calculate_zero_temp_iob(profile: &Profile, time: DateTime<Utc>) -> IOBData {
+    let dia = profile.effective_dia();
+    let curve = profile.curve;
+    let peak = profile.effective_peak_time();
+    let steps = ((dia * 60.0) / 5.0).ceil() as i64;
+
+    let mut total_iob = 0.0;
+    let mut total_activity = 0.0;
+
+    for step in 0..steps {
+        let mins_ago = step as f64 * 5.0 + 2.5;
+        let slot_time = time - chrono::Duration::minutes(step * 5);
+        let scheduled_rate = basal_lookup(profile, slot_time);
+        if scheduled_rate <= 0.0 {
+            continue;
+        }
+
+        let insulin = scheduled_rate * 5.0 / 60.0;
+        let contrib = calculate_iob_contrib(insulin, mins_ago, curve, dia, peak);
+        total_iob -= contrib.iob_contrib;
+        total_activity -= contrib.activity_contrib;
+    }
+
+    IOBData {
+        iob: total_iob,
+        activity: total_activity,
+        basal_iob: total_iob,
+        bolus_iob: 0.0,
+        net_basal_insulin: 0.0,
+        bolus_insulin: 0.0,
+        time,
+        iob_with_zero_temp: None,
+        last_bolus_time: None,
+        last_temp: None,
+    }
+}
+
+/// Total scheduled basal insulin that would be delivered across the same
+/// DIA-horizon window used by [`calculate_zero_temp_iob`], at the
+/// `basal_lookup` rate for each 5-minute slot
+fn scheduled_basal_insulin_over_dia(profile: &Profile, time: DateTime<Utc>) -> f64 {
+    let dia = profile.effective_dia();
+    let steps = ((dia * 60.0) / 5.0).ceil() as i64;
+
+    (0..steps)
+        .map(|step| {
+            let slot_time = time - chrono::Duration::minutes(step * 5);
+            basal_lookup(profile, slot_time) * 5.0 / 60.0
+        })
+        .sum()
+}
+
+impl IOBData {
+    /// Project IOB/activity as if a zero-rate temp basal were set right now
+    /// and held until the DIA horizon
+    ///
+    /// Delegates the decay curve to [`calculate_zero_temp_iob`], then makes
+    /// the result self-consistent with `self`: `net_basal_insulin` is
+    /// adjusted by subtracting the scheduled basal insulin the zero temp
+    /// would claw back over that window, and `last_bolus_time`/`last_temp`
+    /// are carried over from `self` rather than reset, since those describe
+    /// real pump state that doesn't change just because we're projecting.
+    pub fn project_zero_temp(&self, profile: &Profile, now: DateTime<Utc>) -> Box<IOBData> {
+        let mut projected = calculate_zero_temp_iob(profile, now);
+
+        projected.net_basal_insulin = self.net_basal_insulin - scheduled_basal_insulin_over_dia(profile, now);
+        projected.last_bolus_time = self.last_bolus_time;
+        projected.last_temp = self.last_temp.clone();
+
+        Box::new(projected)
+    }
 }
 
 #[cfg(test)]
@@ -240,4 +349,105 @@ mod tests {
         assert!(iob.basal_iob > 0.04);
         assert!(iob.bolus_iob.abs() < 0.01);
     }
+
+    #[test]
+    fn test_large_tagged_temp_basal_dose_categorized_as_basal() {
+        let now = Utc::now();
+        let profile = make_profile(5.0, InsulinCurve::RapidActing);
+
+        // A large net-basal dose (e.g. a big temp-basal-vs-schedule swing)
+        // should still count as basal when tagged, despite exceeding the
+        // old 0.1 U size heuristic.
+        let treatments = vec![Treatment {
+            insulin: Some(0.5),
+            date: now.timestamp_millis(),
+            event_type: Some("TempBasal".to_string()),
+            ..Default::default()
+        }];
+
+        let iob = calculate_total_iob(&profile, &treatments, now).unwrap();
+
+        assert!(iob.basal_iob > 0.4);
+        assert!(iob.bolus_iob.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_small_tagged_bolus_dose_categorized_as_bolus() {
+        let now = Utc::now();
+        let profile = make_profile(5.0, InsulinCurve::RapidActing);
+
+        // A tiny bolus (e.g. a correction micro-bolus) should still count
+        // as a bolus when tagged, despite falling under the old heuristic.
+        let treatments = vec![Treatment {
+            insulin: Some(0.05),
+            date: now.timestamp_millis(),
+            event_type: Some("Bolus".to_string()),
+            ..Default::default()
+        }];
+
+        let iob = calculate_total_iob(&profile, &treatments, now).unwrap();
+
+        assert!(iob.bolus_iob > 0.04);
+        assert!(iob.basal_iob.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_iob_with_zero_temp_absent_without_scheduled_basal() {
+        let now = Utc::now();
+        let profile = make_profile(3.0, InsulinCurve::Bilinear);
+
+        let treatments = vec![Treatment::bolus(2.0, now)];
+
+        let iob = calculate_total_iob(&profile, &treatments, now).unwrap();
+
+        assert!(iob.iob_with_zero_temp.is_none());
+    }
+
+    #[test]
+    fn test_project_zero_temp_carries_over_last_bolus_and_temp() {
+        let now = Utc::now();
+        // Empty basal_profile falls back to a flat `current_basal` rate
+        let profile = Profile {
+            dia: 3.0,
+            curve: InsulinCurve::Bilinear,
+            current_basal: 1.0,
+            ..Default::default()
+        };
+
+        let last_temp = TempBasalState::new(now.timestamp_millis(), 30.0, Some(1.5));
+        let iob = IOBData {
+            net_basal_insulin: 0.4,
+            last_bolus_time: Some(now.timestamp_millis() - 600_000),
+            last_temp: Some(last_temp.clone()),
+            ..IOBData::zero(now)
+        };
+
+        let projected = iob.project_zero_temp(&profile, now);
+
+        assert_eq!(projected.last_bolus_time, iob.last_bolus_time);
+        assert!(projected.last_temp.is_some());
+        assert_eq!(projected.last_temp.as_ref().unwrap().rate, last_temp.rate);
+    }
+
+    #[test]
+    fn test_project_zero_temp_claws_back_scheduled_basal() {
+        let now = Utc::now();
+        let profile = Profile {
+            dia: 3.0,
+            curve: InsulinCurve::Bilinear,
+            current_basal: 1.0,
+            ..Default::default()
+        };
+
+        let iob = IOBData {
+            net_basal_insulin: 0.0,
+            ..IOBData::zero(now)
+        };
+
+        let projected = iob.project_zero_temp(&profile, now);
+
+        // A continuous 1 U/hr schedule over the DIA window delivers
+        // dia-hours worth of insulin, which a zero temp claws fully back
+        assert!((projected.net_basal_insulin - (-profile.dia)).abs() < 0.05);
+    }
 }
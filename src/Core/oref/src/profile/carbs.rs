@@ -1,27 +1,174 @@
 //! Carb ratio schedule lookups
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Timelike, Utc};
 use crate::types::Profile;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A single carb-ratio schedule entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CREntry {
+    /// Minutes since midnight this entry becomes active
+    pub offset: u32,
+    /// Grams of carbs covered by one unit of insulin during this interval
+    pub ratio: f64,
+}
+
+/// A full day's carb-ratio schedule
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CarbRatioProfile {
+    /// Schedule entries, expected sorted by `offset` with the first at 0
+    pub schedule: Vec<CREntry>,
+}
+
 /// Look up the carb ratio at a specific time
-pub fn carb_ratio_lookup(profile: &Profile, _time: DateTime<Utc>) -> f64 {
-    // For now, just return the single carb ratio
-    // Full implementation would support time-based schedules
-    profile.carb_ratio
+///
+/// Mirrors [`super::isf_lookup`]'s schedule design: falls back to
+/// `profile.carb_ratio` when the schedule is empty or malformed (its first
+/// entry doesn't start at midnight).
+pub fn carb_ratio_lookup(profile: &Profile, time: DateTime<Utc>) -> f64 {
+    carb_ratio_lookup_from_schedule(&profile.carb_ratio_profile, time)
+        .unwrap_or(profile.carb_ratio)
+}
+
+/// Look up a carb ratio from a specific schedule
+pub fn carb_ratio_lookup_from_schedule(cr_profile: &CarbRatioProfile, time: DateTime<Utc>) -> Option<f64> {
+    if cr_profile.schedule.is_empty() {
+        return None;
+    }
+
+    let now_minutes = time.hour() * 60 + time.minute();
+
+    let mut schedule: Vec<_> = cr_profile.schedule.iter().collect();
+    schedule.sort_by_key(|e| e.offset);
+
+    // Check first entry starts at midnight
+    if schedule[0].offset != 0 {
+        return None;
+    }
+
+    let mut cr_entry = schedule.last().unwrap();
+
+    for i in 0..schedule.len() {
+        let entry = &schedule[i];
+        let next_offset = if i + 1 < schedule.len() {
+            schedule[i + 1].offset
+        } else {
+            24 * 60
+        };
+
+        if now_minutes >= entry.offset && now_minutes < next_offset {
+            cr_entry = entry;
+            break;
+        }
+    }
+
+    Some(cr_entry.ratio)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
+
+    fn make_profile_with_cr_schedule() -> Profile {
+        Profile {
+            carb_ratio: 10.0,
+            carb_ratio_profile: CarbRatioProfile {
+                schedule: vec![
+                    CREntry { offset: 0, ratio: 12.0 },
+                    CREntry { offset: 360, ratio: 10.0 },  // 06:00 - more insulin-resistant at breakfast
+                    CREntry { offset: 1080, ratio: 14.0 }, // 18:00
+                ],
+            },
+            ..Default::default()
+        }
+    }
 
     #[test]
-    fn test_carb_ratio_lookup() {
+    fn test_carb_ratio_lookup_overnight() {
+        let profile = make_profile_with_cr_schedule();
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 3, 0, 0).unwrap();
+
+        let ratio = carb_ratio_lookup(&profile, time);
+        assert!((ratio - 12.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_carb_ratio_lookup_day() {
+        let profile = make_profile_with_cr_schedule();
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        let ratio = carb_ratio_lookup(&profile, time);
+        assert!((ratio - 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_carb_ratio_lookup_evening() {
+        let profile = make_profile_with_cr_schedule();
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+
+        let ratio = carb_ratio_lookup(&profile, time);
+        assert!((ratio - 14.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_empty_schedule_uses_flat_ratio() {
         let profile = Profile {
-            carb_ratio: 10.0,
+            carb_ratio: 9.0,
+            carb_ratio_profile: CarbRatioProfile::default(),
+            ..Default::default()
+        };
+
+        let ratio = carb_ratio_lookup(&profile, Utc::now());
+        assert!((ratio - 9.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_malformed_schedule_not_starting_at_midnight_falls_back() {
+        let profile = Profile {
+            carb_ratio: 11.0,
+            carb_ratio_profile: CarbRatioProfile {
+                schedule: vec![CREntry { offset: 120, ratio: 8.0 }],
+            },
             ..Default::default()
         };
 
         let ratio = carb_ratio_lookup(&profile, Utc::now());
+        assert!((ratio - 11.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_carb_ratio_lookup_sorts_out_of_order_entries() {
+        // Entries given out of offset order should still resolve correctly
+        let profile = Profile {
+            carb_ratio: 10.0,
+            carb_ratio_profile: CarbRatioProfile {
+                schedule: vec![
+                    CREntry { offset: 1080, ratio: 14.0 },
+                    CREntry { offset: 0, ratio: 12.0 },
+                    CREntry { offset: 360, ratio: 10.0 },
+                ],
+            },
+            ..Default::default()
+        };
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        let ratio = carb_ratio_lookup(&profile, time);
+        assert!((ratio - 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_carb_ratio_lookup_at_entry_boundary() {
+        let profile = make_profile_with_cr_schedule();
+        // Exactly at the 06:00 boundary - should pick up the new entry, not
+        // the one before it
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 6, 0, 0).unwrap();
+
+        let ratio = carb_ratio_lookup(&profile, time);
         assert!((ratio - 10.0).abs() < 0.1);
     }
 }
@@ -0,0 +1,237 @@
+//! Dynamic ISF derived from total daily dose (TDD)
+//!
+//! Instead of a fixed schedule value, sensitivity is recomputed continuously
+//! from recent insulin usage using the "1800 rule" reworked in log space
+//! (see AndroidAPS `DynamicISF`).
+
+use chrono::{DateTime, Utc};
+use crate::determine_basal::InsulinSensitivityMode;
+use crate::tdd::calculate_tdd;
+use crate::types::{Profile, Treatment};
+use super::isf_lookup;
+
+/// Safety clamp multipliers applied to the dynamic ISF relative to the
+/// schedule's static value at this time
+const DYNAMIC_ISF_FLOOR_MULT: f64 = 0.7;
+const DYNAMIC_ISF_CEIL_MULT: f64 = 1.3;
+
+/// Recent total-daily-dose windows used to derive dynamic sensitivity
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TDDInputs {
+    /// Exponentially-weighted average TDD over the last 8 hours, scaled to 24h
+    pub tdd_recent: f64,
+    /// Average TDD over the last 7 days
+    pub tdd_7day: f64,
+}
+
+impl TDDInputs {
+    /// Build TDD inputs from the recent and 7-day windows
+    pub fn new(tdd_recent: f64, tdd_7day: f64) -> Self {
+        Self { tdd_recent, tdd_7day }
+    }
+
+    /// Blend the recent and 7-day windows into a single usable TDD estimate
+    ///
+    /// Weighted 60% recent / 40% 7-day, floored at 75% of the 7-day average
+    /// so a short data window or an implausibly low pump-extrapolated value
+    /// can't swing sensitivity too aggressively.
+    pub fn blended(&self) -> f64 {
+        let blended = 0.6 * self.tdd_recent + 0.4 * self.tdd_7day;
+        let floor = 0.75 * self.tdd_7day;
+        blended.max(floor)
+    }
+}
+
+/// Insulin-peak-dependent divisor for the log-based "1800 rule"
+///
+/// Three-tier table (AndroidAPS DynamicISF): peak 45 -> 75, peak 55 -> 65,
+/// peak 75 -> 55 - faster-acting insulin (earlier peak) gets a higher divisor.
+fn insulin_divisor(peak: u32) -> f64 {
+    if peak <= 45 {
+        75.0
+    } else if peak <= 65 {
+        65.0
+    } else {
+        55.0
+    }
+}
+
+/// Compute a dynamic ISF from TDD and current BG
+///
+/// `variable_sens = 1800 / (TDD * ln(bg/insulin_divisor + 1))`, clamped to
+/// `[floor_mult, ceil_mult] * static_isf` for safety. Falls back to
+/// `static_isf` if the inputs don't support a sane log-space computation.
+pub fn dynamic_isf(
+    bg: f64,
+    tdd: &TDDInputs,
+    peak: u32,
+    static_isf: f64,
+    floor_mult: f64,
+    ceil_mult: f64,
+    adjustment: f64,
+) -> f64 {
+    dynamic_isf_from_tdd(bg, tdd.blended(), peak, static_isf, floor_mult, ceil_mult, adjustment)
+}
+
+/// Compute a dynamic ISF from an already-blended TDD and current BG
+///
+/// Same log-space "1800 rule" as [`dynamic_isf`], but takes a single TDD
+/// value directly instead of re-blending a recent/7-day pair - for callers
+/// (like [`super::effective_isf_lookup`]) whose TDD estimate is already
+/// blended upstream (e.g. [`crate::tdd::calculate_tdd`]).
+///
+/// `adjustment` is a user-facing aggressiveness multiplier (1.0 = unchanged)
+/// applied to the computed variable sensitivity before the safety clamp, so
+/// it can push the result toward either clamp bound but never past it.
+pub fn dynamic_isf_from_tdd(
+    bg: f64,
+    tdd: f64,
+    peak: u32,
+    static_isf: f64,
+    floor_mult: f64,
+    ceil_mult: f64,
+    adjustment: f64,
+) -> f64 {
+    if tdd <= 0.0 {
+        return static_isf;
+    }
+
+    let divisor = insulin_divisor(peak);
+    let log_term = (bg.max(39.0) / divisor + 1.0).ln();
+    if log_term <= 0.0 {
+        return static_isf;
+    }
+
+    let variable_sens = (1800.0 / (tdd * log_term)) * adjustment;
+
+    variable_sens.max(static_isf * floor_mult).min(static_isf * ceil_mult)
+}
+
+/// Effective ISF at a given time and BG, routing through dynamic ISF
+/// (derived from recent total daily dose) when the profile opts in via
+/// `sens_mode`, and falling back to the scheduled static ISF otherwise
+///
+/// This is the entry point autosens and determine-basal call instead of
+/// [`super::isf_lookup`] directly, so a single `Profile` flag switches
+/// sensitivity sourcing everywhere without duplicating the TDD plumbing.
+pub fn effective_isf_lookup(
+    profile: &Profile,
+    treatments: &[Treatment],
+    bg: f64,
+    time: DateTime<Utc>,
+) -> f64 {
+    let static_isf = isf_lookup(profile, time);
+
+    if profile.sens_mode != InsulinSensitivityMode::Dynamic {
+        return static_isf;
+    }
+
+    let tdd = calculate_tdd(treatments, time.timestamp_millis());
+
+    dynamic_isf_from_tdd(
+        bg,
+        tdd.tdd,
+        profile.effective_peak_time(),
+        static_isf,
+        DYNAMIC_ISF_FLOOR_MULT,
+        DYNAMIC_ISF_CEIL_MULT,
+        profile.dynamic_isf_adjustment,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blended_tdd() {
+        let tdd = TDDInputs::new(50.0, 40.0);
+        // 0.6*50 + 0.4*40 = 46
+        assert!((tdd.blended() - 46.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_blended_tdd_floor() {
+        // Implausibly low recent TDD should be floored at 75% of 7-day
+        let tdd = TDDInputs::new(1.0, 40.0);
+        assert!((tdd.blended() - 30.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_dynamic_isf_clamped_to_range() {
+        let tdd = TDDInputs::new(100.0, 100.0); // very high TDD -> low sens
+        let isf = dynamic_isf(150.0, &tdd, 55, 50.0, 0.7, 1.3, 1.0);
+
+        assert!(isf >= 50.0 * 0.7);
+        assert!(isf <= 50.0 * 1.3);
+    }
+
+    #[test]
+    fn test_dynamic_isf_peak_divisor() {
+        let tdd = TDDInputs::new(40.0, 40.0);
+        let ultra = dynamic_isf(150.0, &tdd, 55, 50.0, 0.1, 10.0, 1.0);
+        let rapid = dynamic_isf(150.0, &tdd, 75, 50.0, 0.1, 10.0, 1.0);
+
+        assert!((ultra - rapid).abs() > 0.01);
+    }
+
+    #[test]
+    fn test_zero_tdd_falls_back_to_static() {
+        let tdd = TDDInputs::new(0.0, 0.0);
+        let isf = dynamic_isf(150.0, &tdd, 55, 42.0, 0.7, 1.3, 1.0);
+        assert!((isf - 42.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_dynamic_isf_adjustment_scales_within_clamp() {
+        let tdd = TDDInputs::new(40.0, 40.0);
+        // Wide clamp bounds so the adjustment's effect is visible rather
+        // than swallowed by the safety clamp
+        let unadjusted = dynamic_isf(150.0, &tdd, 55, 50.0, 0.1, 10.0, 1.0);
+        let boosted = dynamic_isf(150.0, &tdd, 55, 50.0, 0.1, 10.0, 1.5);
+
+        assert!((boosted - unadjusted * 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_effective_isf_lookup_uses_static_by_default() {
+        let profile = Profile {
+            sens: 50.0,
+            ..Default::default()
+        };
+
+        let isf = effective_isf_lookup(&profile, &[], 150.0, Utc::now());
+        assert!((isf - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_effective_isf_lookup_routes_through_dynamic_when_enabled() {
+        let now = Utc::now();
+        let profile = Profile {
+            sens: 50.0,
+            sens_mode: InsulinSensitivityMode::Dynamic,
+            ..Default::default()
+        };
+
+        let treatments = vec![Treatment::bolus(10.0, now - chrono::Duration::hours(1))];
+
+        let isf = effective_isf_lookup(&profile, &treatments, 150.0, now);
+
+        // Dynamic ISF is clamped to [0.7, 1.3] * static, so it should differ
+        // from the plain static lookup when TDD data is present
+        assert!(isf >= 50.0 * 0.7 && isf <= 50.0 * 1.3);
+    }
+
+    #[test]
+    fn test_effective_isf_lookup_falls_back_with_no_tdd_data() {
+        let now = Utc::now();
+        let profile = Profile {
+            sens: 50.0,
+            sens_mode: InsulinSensitivityMode::Dynamic,
+            ..Default::default()
+        };
+
+        let isf = effective_isf_lookup(&profile, &[], 150.0, now);
+        assert!((isf - 50.0).abs() < 0.01);
+    }
+}
@@ -6,11 +6,43 @@ use crate::types::{ISFProfile, Profile};
 /// Look up the ISF at a specific time
 pub fn isf_lookup(profile: &Profile, time: DateTime<Utc>) -> f64 {
     isf_lookup_from_schedule(&profile.isf_profile, time)
+        .map(|r| r.sensitivity)
         .unwrap_or(profile.sens)
 }
 
+/// The selected schedule entry from an ISF lookup, carried forward so the
+/// next lookup in a hot loop can check it before re-sorting and re-scanning
+/// the whole schedule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsfLookupResult {
+    /// Sensitivity (mg/dL per unit) of the selected entry
+    pub sensitivity: f64,
+    /// Index of the selected entry within the schedule, as sorted by offset
+    pub entry_index: usize,
+    /// Minutes-since-midnight offset at which the selected entry starts
+    pub offset: u32,
+}
+
 /// Look up ISF from a specific schedule
-pub fn isf_lookup_from_schedule(isf_profile: &ISFProfile, time: DateTime<Utc>) -> Option<f64> {
+///
+/// Batch callers that sweep many timestamped BG datums in order (e.g.
+/// autotune's retrospective categorization) can pass the previous call's
+/// result as `hint`: if `time` still falls within that entry's interval,
+/// the lookup returns immediately without re-sorting or re-scanning the
+/// schedule.
+pub fn isf_lookup_from_schedule(
+    isf_profile: &ISFProfile,
+    time: DateTime<Utc>,
+) -> Option<IsfLookupResult> {
+    isf_lookup_from_schedule_hinted(isf_profile, time, None)
+}
+
+/// [`isf_lookup_from_schedule`] with an optional previous-result fast path
+pub fn isf_lookup_from_schedule_hinted(
+    isf_profile: &ISFProfile,
+    time: DateTime<Utc>,
+    hint: Option<IsfLookupResult>,
+) -> Option<IsfLookupResult> {
     if isf_profile.sensitivities.is_empty() {
         return None;
     }
@@ -26,8 +58,27 @@ pub fn isf_lookup_from_schedule(isf_profile: &ISFProfile, time: DateTime<Utc>) -
         return None;
     }
 
+    // Fast path: if the hinted entry is still within its interval, skip the
+    // full scan. Guard the index against schedule-length changes between
+    // calls so a stale hint can't panic on an out-of-bounds lookup.
+    if let Some(hint) = hint {
+        if let Some(entry) = schedule.get(hint.entry_index) {
+            if entry.offset == hint.offset {
+                let next_offset = if hint.entry_index + 1 < schedule.len() {
+                    schedule[hint.entry_index + 1].offset
+                } else {
+                    24 * 60
+                };
+
+                if now_minutes >= hint.offset && now_minutes < next_offset {
+                    return Some(hint);
+                }
+            }
+        }
+    }
+
     // Find applicable entry
-    let mut isf_entry = schedule.last().unwrap();
+    let mut selected_index = schedule.len() - 1;
 
     for i in 0..schedule.len() {
         let entry = &schedule[i];
@@ -38,12 +89,17 @@ pub fn isf_lookup_from_schedule(isf_profile: &ISFProfile, time: DateTime<Utc>) -
         };
 
         if now_minutes >= entry.offset && now_minutes < next_offset {
-            isf_entry = entry;
+            selected_index = i;
             break;
         }
     }
 
-    Some(isf_entry.sensitivity)
+    let isf_entry = schedule[selected_index];
+    Some(IsfLookupResult {
+        sensitivity: isf_entry.sensitivity,
+        entry_index: selected_index,
+        offset: isf_entry.offset,
+    })
 }
 
 #[cfg(test)]
@@ -104,4 +160,42 @@ mod tests {
         let isf = isf_lookup(&profile, Utc::now());
         assert!((isf - 42.0).abs() < 0.1);
     }
+
+    #[test]
+    fn test_isf_lookup_result_reports_selected_entry() {
+        let profile = make_profile_with_isf_schedule();
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        let result = isf_lookup_from_schedule(&profile.isf_profile, time).unwrap();
+        assert_eq!(result.entry_index, 1);
+        assert_eq!(result.offset, 360);
+        assert!((result.sensitivity - 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_isf_lookup_hint_fast_path_same_interval() {
+        let profile = make_profile_with_isf_schedule();
+        let first_time = Utc.with_ymd_and_hms(2024, 1, 1, 7, 0, 0).unwrap();
+        let first = isf_lookup_from_schedule_hinted(&profile.isf_profile, first_time, None).unwrap();
+
+        // Still within the same 06:00-18:00 interval - hint should be reused as-is
+        let second_time = Utc.with_ymd_and_hms(2024, 1, 1, 16, 0, 0).unwrap();
+        let second = isf_lookup_from_schedule_hinted(&profile.isf_profile, second_time, Some(first)).unwrap();
+
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn test_isf_lookup_hint_falls_back_when_interval_changes() {
+        let profile = make_profile_with_isf_schedule();
+        let first_time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let first = isf_lookup_from_schedule_hinted(&profile.isf_profile, first_time, None).unwrap();
+
+        // Now past 18:00 - the hint no longer applies, so the scan must rerun
+        let second_time = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+        let second = isf_lookup_from_schedule_hinted(&profile.isf_profile, second_time, Some(first)).unwrap();
+
+        assert!((second.sensitivity - 55.0).abs() < 0.1);
+        assert_ne!(second.entry_index, first.entry_index);
+    }
 }
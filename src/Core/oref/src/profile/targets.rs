@@ -35,6 +35,27 @@ pub fn bg_targets_lookup(profile: &Profile, _time: DateTime<Utc>) -> BgTargets {
     targets
 }
 
+/// Shift the target range by the autosens sensitivity ratio
+///
+/// A ratio above 1.0 (resistant) lowers the target for a more aggressive
+/// correction; a ratio below 1.0 (sensitive) raises it to add a safety
+/// margin against lows. Temp targets set by the user take priority and are
+/// left untouched. The result is re-clamped to the same hard floor/ceiling
+/// as an unadjusted lookup.
+pub fn apply_sensitivity_ratio(targets: BgTargets, ratio: f64) -> BgTargets {
+    if targets.temptarget_set || ratio <= 0.0 {
+        return targets;
+    }
+
+    let shift = (ratio - 1.0) * 40.0;
+
+    bound_target_range(BgTargets {
+        min_bg: targets.min_bg - shift,
+        max_bg: targets.max_bg - shift,
+        temptarget_set: targets.temptarget_set,
+    })
+}
+
 /// Apply safety bounds to target range
 fn bound_target_range(mut targets: BgTargets) -> BgTargets {
     // If targets are < 20, assume they're mmol/L and convert
@@ -111,4 +132,35 @@ mod tests {
         assert!((targets.min_bg - 200.0).abs() < 0.1);
         assert!((targets.max_bg - 200.0).abs() < 0.1);
     }
+
+    #[test]
+    fn test_resistant_ratio_lowers_target() {
+        let targets = apply_sensitivity_ratio(
+            BgTargets { min_bg: 100.0, max_bg: 120.0, temptarget_set: false },
+            1.2,
+        );
+
+        assert!((targets.min_bg - 92.0).abs() < 0.1);
+        assert!((targets.max_bg - 112.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_sensitive_ratio_raises_target() {
+        let targets = apply_sensitivity_ratio(
+            BgTargets { min_bg: 100.0, max_bg: 120.0, temptarget_set: false },
+            0.8,
+        );
+
+        assert!((targets.min_bg - 108.0).abs() < 0.1);
+        assert!((targets.max_bg - 128.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_temptarget_set_is_left_untouched() {
+        let original = BgTargets { min_bg: 150.0, max_bg: 150.0, temptarget_set: true };
+        let targets = apply_sensitivity_ratio(original, 1.5);
+
+        assert!((targets.min_bg - 150.0).abs() < 0.1);
+        assert!((targets.max_bg - 150.0).abs() < 0.1);
+    }
 }
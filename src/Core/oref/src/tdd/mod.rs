@@ -0,0 +1,235 @@
+//! Total-daily-dose estimation from historical dosing
+//!
+//! Feeds dynamic ISF (`profile::dynamic_isf`) with a blended TDD computed
+//! from actual pump history rather than a single fixed assumption, mirroring
+//! the AndroidAPS approach of blending a short recent window with a longer
+//! baseline so a single unusual day doesn't swing sensitivity too far.
+//!
+//! Delivered insulin is summed from both boluses and temp-basal delivery
+//! (`rate * overlapping duration`), so a patient running mostly on temp
+//! basals rather than boluses still gets an accurate TDD.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::types::Treatment;
+
+const EIGHT_HOURS_MILLIS: i64 = 8 * 60 * 60 * 1000;
+const ONE_DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const SEVEN_DAYS_MILLIS: i64 = 7 * ONE_DAY_MILLIS;
+
+/// Computed TDD plus its component windows, kept around for logging
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TDDResult {
+    /// The blended value to actually use
+    pub tdd: f64,
+    /// Last 8h of delivery, extrapolated to a 24h rate
+    pub tdd_pump_extrapolated: f64,
+    /// Actual total delivered over the last 24h
+    pub tdd_24h: f64,
+    /// Average daily total over the last 7 days
+    pub tdd_7day: f64,
+}
+
+/// Compute a usable TDD estimate from insulin dosing history
+///
+/// Blends the pump-extrapolated recent rate with the 7-day average:
+/// `tdd = 0.4 * tdd_7day + 0.6 * tdd_pump_extrapolated`. Falls back to
+/// `0.75 * tdd_7day` when there isn't yet 8h of history to extrapolate from,
+/// or when the extrapolated value is implausible (less than half or more
+/// than double the 7-day mean) versus the 7-day mean.
+pub fn calculate_tdd(history: &[Treatment], now_millis: i64) -> TDDResult {
+    let tdd_pump_extrapolated = sum_insulin_since(history, now_millis, EIGHT_HOURS_MILLIS) * 3.0;
+    let tdd_24h = sum_insulin_since(history, now_millis, ONE_DAY_MILLIS);
+
+    // Scale by the days of history actually present in the window (at least
+    // 1, so a fresh loop doesn't divide by a fraction of a day), rather than
+    // assuming a full 7 days are always available
+    let days_of_data = (oldest_event_age(history, now_millis).min(SEVEN_DAYS_MILLIS) as f64
+        / ONE_DAY_MILLIS as f64)
+        .ceil()
+        .max(1.0);
+    let tdd_7day = sum_insulin_since(history, now_millis, SEVEN_DAYS_MILLIS) / days_of_data;
+
+    let has_enough_data = oldest_event_age(history, now_millis) >= EIGHT_HOURS_MILLIS;
+
+    let implausible = tdd_7day > 0.0
+        && (tdd_pump_extrapolated < tdd_7day * 0.5 || tdd_pump_extrapolated > tdd_7day * 2.0);
+
+    let tdd = if !has_enough_data || implausible {
+        0.75 * tdd_7day
+    } else {
+        0.4 * tdd_7day + 0.6 * tdd_pump_extrapolated
+    };
+
+    TDDResult {
+        tdd,
+        tdd_pump_extrapolated,
+        tdd_24h,
+        tdd_7day,
+    }
+}
+
+/// Sum insulin delivered in the `window_millis` leading up to `now_millis`
+fn sum_insulin_since(history: &[Treatment], now_millis: i64, window_millis: i64) -> f64 {
+    let cutoff = now_millis - window_millis;
+    history
+        .iter()
+        .map(|t| treatment_insulin_in_window(t, cutoff, now_millis))
+        .sum()
+}
+
+/// Insulin delivered by a single treatment within `[cutoff, now_millis]`
+///
+/// Boluses count in full if their timestamp falls in the window. Temp basals
+/// contribute `rate * overlapping_hours`, clipped to whatever portion of
+/// their duration actually falls inside the window, so a temp that started
+/// before `cutoff` or is still running past `now_millis` is only counted for
+/// the slice that occurred in-window.
+fn treatment_insulin_in_window(t: &Treatment, cutoff: i64, now_millis: i64) -> f64 {
+    if t.is_temp_basal() {
+        let start = t.effective_date();
+        let rate = t.rate.unwrap_or(0.0);
+        let duration_minutes = t.duration.unwrap_or(0.0);
+        let end = start + (duration_minutes * 60_000.0) as i64;
+
+        let overlap_minutes = (end.min(now_millis) - start.max(cutoff)).max(0) as f64 / 60_000.0;
+        return rate * overlap_minutes / 60.0;
+    }
+
+    let date = t.effective_date();
+    if date >= cutoff && date <= now_millis {
+        t.insulin.unwrap_or(0.0)
+    } else {
+        0.0
+    }
+}
+
+/// Age in millis of the oldest event in history (0 if history is empty)
+fn oldest_event_age(history: &[Treatment], now_millis: i64) -> i64 {
+    history
+        .iter()
+        .map(|t| now_millis - t.effective_date())
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_falls_back_with_insufficient_data() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        // Only 1 hour of history - not enough to extrapolate an 8h window
+        let history = vec![Treatment::bolus(2.0, now - Duration::hours(1))];
+
+        let result = calculate_tdd(&history, now_millis);
+
+        // Not enough history to extrapolate an 8h window, so it falls back
+        // to 75% of the (still sparse) 7-day average rather than the
+        // extrapolated rate.
+        assert!((result.tdd - 0.75 * result.tdd_7day).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_blends_extrapolated_and_weekly_average() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        let mut history = Vec::new();
+        // 8h ago to now: 2U/hr steady, so 16U in the last 8h -> extrapolates to 48U/day
+        for h in 0..8 {
+            history.push(Treatment::bolus(2.0, now - Duration::hours(h + 1)));
+        }
+        // Plus enough history further back to make the 7-day average ~48U/day too
+        for d in 1..7 {
+            history.push(Treatment::bolus(48.0, now - Duration::days(d)));
+        }
+
+        let result = calculate_tdd(&history, now_millis);
+
+        assert!((result.tdd_pump_extrapolated - 48.0).abs() < 0.5);
+        assert!((result.tdd - 48.0).abs() < 3.0);
+    }
+
+    #[test]
+    fn test_implausible_extrapolation_falls_back_to_weekly() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        let mut history = Vec::new();
+        // A single big bolus in the last 8h makes the extrapolated rate spike
+        history.push(Treatment::bolus(20.0, now - Duration::hours(1)));
+        // But the 7-day history shows a steady, much lower baseline
+        for d in 1..8 {
+            history.push(Treatment::bolus(20.0, now - Duration::days(d)));
+        }
+
+        let result = calculate_tdd(&history, now_millis);
+
+        // 20U/8h -> 60U/day extrapolated, vs ~20U/day 7-day average: implausible
+        assert!(result.tdd_pump_extrapolated > result.tdd_7day * 2.0);
+        assert!((result.tdd - 0.75 * result.tdd_7day).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_short_history_scales_by_days_present_not_a_fixed_seven() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        // Only 2 days of history (steady 48U/day), well short of a full week
+        let mut history = Vec::new();
+        for d in 0..2 {
+            history.push(Treatment::bolus(48.0, now - Duration::days(d) - Duration::hours(1)));
+        }
+
+        let result = calculate_tdd(&history, now_millis);
+
+        // Dividing by a fixed 7 would read this as ~14U/day; scaling by the
+        // 2 days actually present should read it close to the true 48U/day
+        assert!((result.tdd_7day - 48.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_temp_basal_delivery_counts_toward_tdd() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        // A 2h temp basal at 1.5U/hr entirely within the last 8h -> 3U delivered
+        let history = vec![Treatment::temp_basal(1.5, 120.0, now - Duration::hours(4))];
+
+        let result = calculate_tdd(&history, now_millis);
+
+        assert!((result.tdd_24h - 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_temp_basal_spanning_window_boundary_is_only_counted_in_window() {
+        let now = Utc::now();
+        let now_millis = now.timestamp_millis();
+
+        // A 4h temp basal at 1.0U/hr starting 2h before "now" - only 2h of it
+        // falls within an 8h-ago..now window, so only 2U should count.
+        let history = vec![Treatment::temp_basal(1.0, 240.0, now - Duration::hours(2))];
+
+        let result = calculate_tdd(&history, now_millis);
+
+        assert!((result.tdd_24h - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_empty_history_yields_zero() {
+        let now_millis = Utc::now().timestamp_millis();
+        let result = calculate_tdd(&[], now_millis);
+
+        assert_eq!(result.tdd, 0.0);
+        assert_eq!(result.tdd_pump_extrapolated, 0.0);
+        assert_eq!(result.tdd_7day, 0.0);
+    }
+}
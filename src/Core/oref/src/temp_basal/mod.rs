@@ -0,0 +1,240 @@
+//! Temp basal safety layer
+//!
+//! Wraps a raw suggested basal rate with the safety clamping and pump-rounding
+//! that real hardware requires before it becomes a `Treatment`/`CurrentTemp`:
+//! never exceed a computed safe ceiling, always round to a deliverable
+//! increment, and don't churn the pump with a near-identical temp.
+
+use crate::types::{Profile, TempBasalState};
+use crate::utils::round_basal;
+
+/// Default multiplier applied to `max_daily_basal` when the profile doesn't set one
+const DEFAULT_MAX_DAILY_SAFETY_MULTIPLIER: f64 = 3.0;
+/// Default multiplier applied to `current_basal` when the profile doesn't set one
+const DEFAULT_CURRENT_BASAL_SAFETY_MULTIPLIER: f64 = 4.0;
+
+/// Minutes of remaining temp duration below which a new temp is always issued
+const MIN_REMAINING_MINUTES_TO_SUPPRESS: f64 = 20.0;
+/// Fractional tolerance within which a requested rate is "close enough" to a running temp
+const SUPPRESS_RATE_TOLERANCE: f64 = 0.20;
+
+/// The outcome of a temp-basal safety decision
+#[derive(Debug, Clone)]
+pub struct TempBasalRecommendation {
+    /// Rate to set (U/hr), already rounded to a pump-legal increment
+    pub rate: f64,
+    /// Duration to set (minutes)
+    pub duration: f64,
+    /// Whether a new temp should actually be issued (false = leave the running temp alone)
+    pub should_set: bool,
+    /// Human-readable explanation of how the rate/decision was reached
+    pub reason: String,
+}
+
+/// Compute the maximum basal rate that is safe to deliver
+///
+/// `min(max_basal, max_daily_safety_multiplier * max_daily_basal, current_basal_safety_multiplier * current_basal)`
+pub fn get_max_safe_basal(profile: &Profile) -> f64 {
+    let max_daily_multiplier = if profile.max_daily_safety_multiplier > 0.0 {
+        profile.max_daily_safety_multiplier
+    } else {
+        DEFAULT_MAX_DAILY_SAFETY_MULTIPLIER
+    };
+
+    let current_basal_multiplier = if profile.current_basal_safety_multiplier > 0.0 {
+        profile.current_basal_safety_multiplier
+    } else {
+        DEFAULT_CURRENT_BASAL_SAFETY_MULTIPLIER
+    };
+
+    profile
+        .max_basal
+        .min(max_daily_multiplier * profile.max_daily_basal)
+        .min(current_basal_multiplier * profile.current_basal)
+}
+
+/// Decide whether/how to set a temp basal
+///
+/// Clamps negative rates to zero, caps at `get_max_safe_basal`, rounds to the
+/// pump's increment, and suppresses issuing a near-identical temp when the
+/// current one still has more than 20 minutes left.
+pub fn set_temp_basal(
+    rate: f64,
+    duration: f64,
+    profile: &Profile,
+    current_temp: Option<&TempBasalState>,
+) -> TempBasalRecommendation {
+    let mut reason = String::new();
+
+    let clamped = if rate < 0.0 {
+        reason.push_str("requested rate < 0, clamping to 0; ");
+        0.0
+    } else {
+        rate
+    };
+
+    let max_safe = get_max_safe_basal(profile);
+    let capped = if clamped > max_safe {
+        reason.push_str(&format!("requested {:.3} > max safe basal {:.3}, capping; ", clamped, max_safe));
+        max_safe
+    } else {
+        clamped
+    };
+
+    let rounded = round_basal(capped, profile);
+
+    if let Some(temp) = current_temp {
+        let remaining = temp.duration;
+        if remaining > MIN_REMAINING_MINUTES_TO_SUPPRESS && temp.rate.is_some() {
+            let running_rate = temp.rate.unwrap_or(0.0);
+            // Small epsilon keeps the inclusive 20% bound from being lost to
+            // float error in pump rounding (e.g. 24 * 0.05 > 1.2)
+            let within_tolerance = running_rate > 0.0
+                && (rounded - running_rate).abs() <= running_rate * SUPPRESS_RATE_TOLERANCE + 1e-9;
+
+            if within_tolerance {
+                reason.push_str(&format!(
+                    "{:.0}m left on {:.3}U/hr temp, within 20% of requested {:.3}U/hr, no change",
+                    remaining, running_rate, rounded
+                ));
+
+                return TempBasalRecommendation {
+                    rate: running_rate,
+                    duration: remaining,
+                    should_set: false,
+                    reason,
+                };
+            }
+        }
+    }
+
+    reason.push_str(&format!("setting temp {:.3}U/hr for {:.0}m", rounded, duration));
+
+    TempBasalRecommendation {
+        rate: rounded,
+        duration,
+        should_set: true,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_profile() -> Profile {
+        Profile {
+            max_basal: 5.0,
+            max_daily_basal: 1.0,
+            current_basal: 1.0,
+            max_daily_safety_multiplier: 3.0,
+            current_basal_safety_multiplier: 4.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_max_safe_basal_picks_tightest_limit() {
+        let profile = make_profile();
+        // max_basal=5, 3*1=3, 4*1=4 -> tightest is 3
+        assert!((get_max_safe_basal(&profile) - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_max_safe_basal_defaults_multipliers() {
+        let profile = Profile {
+            max_basal: 10.0,
+            max_daily_basal: 1.0,
+            current_basal: 1.0,
+            max_daily_safety_multiplier: 0.0,
+            current_basal_safety_multiplier: 0.0,
+            ..Default::default()
+        };
+        // Defaults: 3*1=3, 4*1=4 -> tightest is 3
+        assert!((get_max_safe_basal(&profile) - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_negative_rate_clamped_to_zero() {
+        let profile = make_profile();
+        let rec = set_temp_basal(-1.0, 30.0, &profile, None);
+        assert_eq!(rec.rate, 0.0);
+        assert!(rec.should_set);
+    }
+
+    #[test]
+    fn test_rate_capped_at_max_safe_basal() {
+        let profile = make_profile();
+        let rec = set_temp_basal(10.0, 30.0, &profile, None);
+        assert!(rec.rate <= get_max_safe_basal(&profile) + 0.001);
+    }
+
+    #[test]
+    fn test_suppresses_near_identical_temp() {
+        let profile = make_profile();
+        let current = TempBasalState::new(0, 25.0, Some(1.0));
+
+        // 1.1 is within 20% of 1.0 and 25 min > 20 min left
+        let rec = set_temp_basal(1.1, 30.0, &profile, Some(&current));
+
+        assert!(!rec.should_set);
+        assert_eq!(rec.rate, 1.0);
+        assert_eq!(rec.duration, 25.0);
+    }
+
+    #[test]
+    fn test_issues_new_temp_when_rate_differs_enough() {
+        let profile = make_profile();
+        let current = TempBasalState::new(0, 25.0, Some(1.0));
+
+        // 2.0 is far outside 20% tolerance of 1.0
+        let rec = set_temp_basal(2.0, 30.0, &profile, Some(&current));
+
+        assert!(rec.should_set);
+    }
+
+    #[test]
+    fn test_issues_new_temp_when_little_time_remains() {
+        let profile = make_profile();
+        let current = TempBasalState::new(0, 10.0, Some(1.0));
+
+        // Only 10 min left, below the 20 min suppression threshold
+        let rec = set_temp_basal(1.05, 30.0, &profile, Some(&current));
+
+        assert!(rec.should_set);
+    }
+
+    #[test]
+    fn test_issues_new_temp_at_exactly_twenty_minutes_remaining() {
+        let profile = make_profile();
+        // Suppression requires *more* than 20 minutes left, not "at least"
+        let current = TempBasalState::new(0, 20.0, Some(1.0));
+
+        let rec = set_temp_basal(1.05, 30.0, &profile, Some(&current));
+
+        assert!(rec.should_set);
+    }
+
+    #[test]
+    fn test_suppresses_at_exactly_twenty_percent_tolerance_boundary() {
+        let profile = make_profile();
+        let current = TempBasalState::new(0, 25.0, Some(1.0));
+
+        // 1.2 is exactly 20% above 1.0, which is within the inclusive tolerance
+        let rec = set_temp_basal(1.2, 30.0, &profile, Some(&current));
+
+        assert!(!rec.should_set);
+    }
+
+    #[test]
+    fn test_issues_new_temp_when_running_rate_is_zero() {
+        let profile = make_profile();
+        // A zero-rate running temp has no meaningful tolerance band to be
+        // "close enough" to, so it should never suppress a new request
+        let current = TempBasalState::new(0, 25.0, Some(0.0));
+
+        let rec = set_temp_basal(0.0, 30.0, &profile, Some(&current));
+
+        assert!(rec.should_set);
+    }
+}
@@ -138,4 +138,8 @@ pub struct COBResult {
 
     /// Slope from min deviation
     pub slope_from_min: f64,
+
+    /// ISF actually used for the deviation→carb conversion (static or,
+    /// when `Profile.sens_mode` is `Dynamic`, the TDD-derived dynamic ISF)
+    pub effective_isf: f64,
 }
@@ -46,6 +46,16 @@ pub struct Treatment {
     #[cfg_attr(feature = "serde", serde(default))]
     pub journal_carbs: Option<f64>,
 
+    /// Expected total absorption window for this carb entry (minutes);
+    /// drives the piecewise absorption curve alongside `delay`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub absorption_time: Option<f64>,
+
+    /// Minutes after this carb entry before absorption begins (e.g. for
+    /// high-fat meals or extended boluses paired with food)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub delay: Option<f64>,
+
     /// Temp basal rate (U/hr)
     #[cfg_attr(feature = "serde", serde(default))]
     pub rate: Option<f64>,
@@ -71,6 +81,8 @@ impl Default for Treatment {
             ns_carbs: None,
             bw_carbs: None,
             journal_carbs: None,
+            absorption_time: None,
+            delay: None,
             rate: None,
             duration: None,
             event_type: None,
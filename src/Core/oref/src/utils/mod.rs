@@ -2,9 +2,11 @@
 
 mod round;
 mod time;
+mod units;
 
 pub use round::{round_basal, round_value};
 pub use time::{parse_timestamp, format_timestamp};
+pub use units::{format_bg, BgUnits};
 
 
 /// Round a value to a specific number of decimal places
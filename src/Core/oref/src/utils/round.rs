@@ -6,11 +6,29 @@ use crate::types::Profile;
 ///
 /// Different pump models support different precision levels:
 /// - Older Medtronic (5xx series): 0.05 U/hr increments
-/// - Newer Medtronic (7xx series): 0.025 U/hr increments
+/// - Newer Medtronic (7xx/x23/x54 series): tiered increments that get
+///   coarser as the rate grows (see [`round_to_tiered_increment`])
 /// - Omnipod: 0.05 U/hr increments
+///
+/// `profile.basal_increment`, when set, overrides all model-based guessing
+/// with a single flat increment - for pumps not in the known-model list
+/// above, or test rigs that want an exact, explicit step size.
 pub fn round_basal(rate: f64, profile: &Profile) -> f64 {
-    let increment = get_pump_increment(profile);
-    round_to_increment(rate, increment)
+    if let Some(increment) = profile.basal_increment {
+        return round_to_increment(rate, increment);
+    }
+
+    let is_newer_medtronic_pump = profile
+        .model
+        .as_deref()
+        .map(is_newer_medtronic)
+        .unwrap_or(false);
+
+    if is_newer_medtronic_pump {
+        round_to_tiered_increment(rate)
+    } else {
+        round_to_increment(rate, get_pump_increment(profile))
+    }
 }
 
 /// Round a value to the nearest increment
@@ -40,17 +58,34 @@ fn is_newer_medtronic(model: &str) -> bool {
     newer_models.iter().any(|m| model.contains(m))
 }
 
-/// Round a rate to the specified increment
+/// Round a rate to a single flat increment
+///
+/// Used for pump models with one programmable increment regardless of rate
+/// (Omnipod, older Medtronic), except that basal rates of 10 U/hr or more
+/// are always programmed in 0.1 U/hr steps on these pumps too.
 fn round_to_increment(rate: f64, increment: f64) -> f64 {
-    // Special rounding rules for high rates (>10 U/hr)
-    if rate > 10.0 {
-        // Round to 0.1 for high rates
+    if rate >= 10.0 {
         (rate * 10.0).round() / 10.0
     } else {
         (rate / increment).round() * increment
     }
 }
 
+/// Round a rate using the x23/x54 newer-Medtronic tiered increments
+///
+/// These pumps change their programmable increment by rate magnitude:
+/// 0.025 U/hr below 1 U/hr, 0.05 U/hr from 1 up to 9.95 U/hr, and 0.1 U/hr
+/// at 10 U/hr and above, matching how the pump actually accepts rates.
+fn round_to_tiered_increment(rate: f64) -> f64 {
+    if rate >= 10.0 {
+        (rate * 10.0).round() / 10.0
+    } else if rate >= 1.0 {
+        (rate / 0.05).round() * 0.05
+    } else {
+        (rate / 0.025).round() * 0.025
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,6 +113,36 @@ mod tests {
         assert!((round_basal(0.040, &profile) - 0.05).abs() < 0.001);
     }
 
+    #[test]
+    fn test_round_basal_newer_medtronic_band_boundaries() {
+        let profile = Profile {
+            model: Some("554".to_string()),
+            ..Default::default()
+        };
+
+        // Just below the 1.0 boundary: 0.025 U band
+        assert!((round_basal(0.9, &profile) - 0.9).abs() < 0.001);
+        // At the 1.0 boundary: 0.05 U band
+        assert!((round_basal(1.0, &profile) - 1.0).abs() < 0.001);
+        // Just below the 10.0 boundary: still 0.05 U band
+        assert!((round_basal(9.95, &profile) - 9.95).abs() < 0.001);
+        // At the 10.0 boundary: 0.1 U band
+        assert!((round_basal(10.0, &profile) - 10.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_round_basal_omnipod_ignores_rate_bands() {
+        let profile = Profile {
+            model: Some("Omnipod".to_string()),
+            ..Default::default()
+        };
+
+        // Omnipod keeps a single 0.05 U increment below 10 U/hr regardless
+        // of rate magnitude - no 0.025 U tier.
+        assert!((round_basal(0.9, &profile) - 0.9).abs() < 0.001);
+        assert!((round_basal(0.975, &profile) - 1.0).abs() < 0.001);
+    }
+
     #[test]
     fn test_round_basal_high_rate() {
         let profile = Profile::default();
@@ -87,6 +152,42 @@ mod tests {
         assert!((round_basal(10.86, &profile) - 10.9).abs() < 0.001);
     }
 
+    #[test]
+    fn test_round_basal_increment_override_wins_over_model() {
+        let profile = Profile {
+            model: Some("554".to_string()), // would otherwise use tiered increments
+            basal_increment: Some(0.1),
+            ..Default::default()
+        };
+
+        assert!((round_basal(0.43, &profile) - 0.4).abs() < 0.001);
+        assert!((round_basal(0.46, &profile) - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_round_basal_increment_override_still_obeys_high_rate_carveout() {
+        // The override picks the increment, but the >=10 U/hr carve-out is
+        // a hardware constraint (real pumps only accept 0.1 U steps that
+        // high), not a preference, so it still applies even with an
+        // explicit override set below that threshold.
+        let profile = Profile {
+            basal_increment: Some(0.2),
+            ..Default::default()
+        };
+
+        assert!((round_basal(10.43, &profile) - 10.4).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_round_basal_unknown_model_uses_flat_increment() {
+        let profile = Profile {
+            model: Some("Totally-Unknown-Pump".to_string()),
+            ..Default::default()
+        };
+
+        assert!((round_basal(0.83, &profile) - 0.85).abs() < 0.001);
+    }
+
     #[test]
     fn test_round_value() {
         assert!((round_value(1.2345, 2) - 1.23).abs() < 0.001);
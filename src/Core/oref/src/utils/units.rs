@@ -0,0 +1,39 @@
+//! BG unit formatting for user-facing output
+//!
+//! Internal math always stays in mg/dL; this only controls how a BG value is
+//! rendered into a `result.reason` string.
+
+/// Units a profile wants BG values formatted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BgUnits {
+    #[default]
+    MgDl,
+    MmolL,
+}
+
+/// Format an internal mg/dL value for display in the given units
+///
+/// mg/dL values are shown with no decimal places; mmol/L values are
+/// `value / 18.0`, rounded to one decimal place, matching the reference
+/// `convert_bg` helper.
+pub fn format_bg(value: f64, out_units: BgUnits) -> String {
+    match out_units {
+        BgUnits::MgDl => format!("{:.0}", value),
+        BgUnits::MmolL => format!("{:.1}", value / 18.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bg_mgdl() {
+        assert_eq!(format_bg(154.4, BgUnits::MgDl), "154");
+    }
+
+    #[test]
+    fn test_format_bg_mmol() {
+        assert_eq!(format_bg(180.0, BgUnits::MmolL), "10.0");
+    }
+}
@@ -150,6 +150,66 @@ pub fn calculate_autosens(
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+// ============================================================================
+// TDD (Total Daily Dose) Calculation
+// ============================================================================
+
+/// Calculate total daily dose from treatment history
+///
+/// # Arguments
+/// * `treatments_json` - JSON string containing array of Treatment objects
+/// * `time_millis` - Current time as Unix milliseconds
+///
+/// # Returns
+/// JSON string containing TDDResult (blended tdd plus its component windows)
+#[wasm_bindgen]
+pub fn calculate_tdd(treatments_json: &str, time_millis: i64) -> Result<String, JsValue> {
+    let treatments: Vec<Treatment> = serde_json::from_str(treatments_json)
+        .map_err(|e| JsValue::from_str(&format!("Treatments parse error: {}", e)))?;
+
+    let result = crate::tdd::calculate_tdd(&treatments, time_millis);
+
+    serde_json::to_string(&result)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+// ============================================================================
+// Autotune
+// ============================================================================
+
+/// Tune DIA, insulin peak, basal schedule, ISF, and carb ratio from history
+///
+/// # Arguments
+/// * `profile_json` - JSON string containing the starting Profile data
+/// * `glucose_json` - JSON string containing array of GlucoseReading objects (24h+)
+/// * `treatments_json` - JSON string containing array of Treatment objects (24h+)
+/// * `timeshift_minutes` - Minutes to shift every timestamp before bucketing by
+///   hour, so basal buckets line up with local midnight
+///
+/// # Returns
+/// JSON string containing AutotuneResult (tuned profile plus DIA/peak sweep tables)
+#[wasm_bindgen]
+pub fn calculate_autotune(
+    profile_json: &str,
+    glucose_json: &str,
+    treatments_json: &str,
+    timeshift_minutes: i64,
+) -> Result<String, JsValue> {
+    let profile: Profile = serde_json::from_str(profile_json)
+        .map_err(|e| JsValue::from_str(&format!("Profile parse error: {}", e)))?;
+
+    let glucose: Vec<GlucoseReading> = serde_json::from_str(glucose_json)
+        .map_err(|e| JsValue::from_str(&format!("Glucose parse error: {}", e)))?;
+
+    let treatments: Vec<Treatment> = serde_json::from_str(treatments_json)
+        .map_err(|e| JsValue::from_str(&format!("Treatments parse error: {}", e)))?;
+
+    let result = crate::autotune::calculate_autotune(&profile, &glucose, &treatments, timeshift_minutes);
+
+    serde_json::to_string(&result)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 // ============================================================================
 // Determine Basal (Main Algorithm)
 // ============================================================================
@@ -178,6 +238,16 @@ pub struct DetermineBasalInputsJson {
     #[serde(default)]
     pub meal_data: MealData,
 
+    /// Treatment history, used to derive dynamic ISF from TDD when the
+    /// profile's `sens_mode` opts into it
+    #[serde(default)]
+    pub treatments: Vec<Treatment>,
+
+    /// Insulin already committed but not yet reflected in IOB, discounted
+    /// from the SMB recommendation so an in-flight delivery isn't double-dosed
+    #[serde(default)]
+    pub pending_insulin: f64,
+
     /// Whether micro bolus (SMB) is allowed
     #[serde(default)]
     pub micro_bolus_allowed: bool,
@@ -211,6 +281,8 @@ pub fn determine_basal(inputs_json: &str) -> Result<String, JsValue> {
         profile: &inputs.profile,
         autosens_data: &inputs.autosens_data,
         meal_data: &inputs.meal_data,
+        treatments: &inputs.treatments,
+        pending_insulin: inputs.pending_insulin,
         micro_bolus_allowed: inputs.micro_bolus_allowed,
         current_time,
     };
@@ -257,6 +329,8 @@ pub fn determine_basal_simple(
         profile: &profile,
         autosens_data: &autosens_data,
         meal_data: &meal_data,
+        treatments: &[],
+        pending_insulin: 0.0,
         micro_bolus_allowed,
         current_time: None,
     };
@@ -291,6 +365,80 @@ pub fn calculate_glucose_status(glucose_json: &str) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+// ============================================================================
+// Glucose Ingestion
+// ============================================================================
+
+/// Normalize raw vendor CGM/pump records (e.g. Medtronic `GlucoseSensorData`)
+/// into clean [`GlucoseReading`] JSON
+///
+/// # Arguments
+/// * `records_json` - JSON array of raw vendor records, each carrying a `_type` field
+/// * `offset_minutes` - Local UTC offset applied to any record timestamped as a local-time string
+///
+/// # Returns
+/// JSON string containing `{readings, accepted, rejected}`
+#[wasm_bindgen]
+pub fn normalize_glucose(records_json: &str, offset_minutes: i64) -> Result<String, JsValue> {
+    let records: Vec<serde_json::Value> = serde_json::from_str(records_json)
+        .map_err(|e| JsValue::from_str(&format!("Records parse error: {}", e)))?;
+
+    let result = crate::ingest::normalize_glucose(&records, offset_minutes);
+
+    serde_json::to_string(&result)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+// ============================================================================
+// Basal Safety
+// ============================================================================
+
+/// Compute the maximum basal rate that is safe to deliver for a profile
+///
+/// `min(max_basal, max_daily_safety_multiplier * max_daily_basal,
+/// current_basal_safety_multiplier * current_basal)`, defaulting the
+/// multipliers to 3 and 4 respectively when absent/zero - see
+/// [`crate::temp_basal::get_max_safe_basal`]. `determine_basal` already runs
+/// every proposed rate through this same ceiling internally; this binding
+/// lets callers audit or independently re-check a rate themselves.
+///
+/// # Arguments
+/// * `profile_json` - JSON string containing Profile data
+///
+/// # Returns
+/// JSON number: the max safe basal rate (U/hr)
+#[wasm_bindgen]
+pub fn max_safe_basal(profile_json: &str) -> Result<String, JsValue> {
+    let profile: Profile = serde_json::from_str(profile_json)
+        .map_err(|e| JsValue::from_str(&format!("Profile parse error: {}", e)))?;
+
+    let max_safe = crate::temp_basal::get_max_safe_basal(&profile);
+
+    serde_json::to_string(&max_safe)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Round a requested basal rate to the pump's supported increment
+///
+/// See [`crate::utils::round_basal`] for the per-model increment rules.
+///
+/// # Arguments
+/// * `rate` - Requested rate (U/hr)
+/// * `profile_json` - JSON string containing Profile data
+///
+/// # Returns
+/// JSON number: the rounded rate (U/hr)
+#[wasm_bindgen]
+pub fn round_basal(rate: f64, profile_json: &str) -> Result<String, JsValue> {
+    let profile: Profile = serde_json::from_str(profile_json)
+        .map_err(|e| JsValue::from_str(&format!("Profile parse error: {}", e)))?;
+
+    let rounded = crate::utils::round_basal(rate, &profile);
+
+    serde_json::to_string(&rounded)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 // ============================================================================
 // Version and Info
 // ============================================================================
@@ -329,4 +477,93 @@ mod tests {
         let version = oref_version();
         assert!(!version.is_empty());
     }
+
+    #[test]
+    fn test_calculate_tdd_empty_history() {
+        let result = calculate_tdd("[]", 0).unwrap();
+        assert!(result.contains("\"tdd\""));
+    }
+
+    #[test]
+    fn test_calculate_tdd_rejects_invalid_json() {
+        assert!(calculate_tdd("not json", 0).is_err());
+    }
+
+    fn minimal_profile_json() -> &'static str {
+        r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 2.0,
+            "maxBasal": 4.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0
+        }"#
+    }
+
+    #[test]
+    fn test_calculate_autotune_empty_history_returns_tuned_profile() {
+        let result = calculate_autotune(minimal_profile_json(), "[]", "[]", 0).unwrap();
+        assert!(result.contains("\"tunedProfile\""));
+        assert!(result.contains("\"diaDeviations\""));
+        assert!(result.contains("\"peakDeviations\""));
+    }
+
+    #[test]
+    fn test_calculate_autotune_rejects_invalid_profile_json() {
+        assert!(calculate_autotune("not json", "[]", "[]", 0).is_err());
+    }
+
+    #[test]
+    fn test_normalize_glucose_accepts_recognized_record() {
+        let records = r#"[{"_type": "GlucoseSensorData", "sgv": 120.0, "date": 1700000000000}]"#;
+        let result = normalize_glucose(records, 0).unwrap();
+
+        assert!(result.contains("\"accepted\":1"));
+        assert!(result.contains("\"rejected\":0"));
+    }
+
+    #[test]
+    fn test_normalize_glucose_rejects_invalid_json() {
+        assert!(normalize_glucose("not json", 0).is_err());
+    }
+
+    #[test]
+    fn test_max_safe_basal_picks_tightest_limit() {
+        let profile_json = r#"{
+            "dia": 3.0,
+            "currentBasal": 1.0,
+            "maxIob": 10.0,
+            "maxDailyBasal": 1.0,
+            "maxBasal": 5.0,
+            "minBg": 100.0,
+            "maxBg": 120.0,
+            "sens": 50.0,
+            "carbRatio": 10.0,
+            "maxDailySafetyMultiplier": 3.0,
+            "currentBasalSafetyMultiplier": 4.0
+        }"#;
+
+        // max_basal=5, 3*1=3, 4*1=4 -> tightest is 3
+        let result: f64 = serde_json::from_str(&max_safe_basal(profile_json).unwrap()).unwrap();
+        assert!((result - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_max_safe_basal_rejects_invalid_profile_json() {
+        assert!(max_safe_basal("not json").is_err());
+    }
+
+    #[test]
+    fn test_round_basal_rounds_to_default_increment() {
+        let result: f64 = serde_json::from_str(&round_basal(0.83, minimal_profile_json()).unwrap()).unwrap();
+        assert!((result - 0.85).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_round_basal_rejects_invalid_profile_json() {
+        assert!(round_basal(0.83, "not json").is_err());
+    }
 }